@@ -0,0 +1,866 @@
+use std::{fmt::Write, sync::Arc};
+
+use poem::{web::Data, Request};
+use poem_openapi::{
+    param::{Path, Query},
+    payload::{Json, PlainText},
+    OpenApi,
+};
+use poem_openapi_derive::{ApiResponse, Enum, Object};
+
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::pokemon_api::{
+    self, CircuitBreakerStatus, CircuitState, NamedApiResource, PokedexBackend, PokedexError,
+    PokemonFilter,
+};
+
+mod batch;
+mod cursor;
+mod evolution;
+mod problem;
+mod search;
+mod sort;
+mod species;
+mod types;
+
+use batch::{BatchItem, BatchRequest, BatchResponse};
+use cursor::{Cursor, CursorCodec};
+use evolution::{EvolutionNode, EvolutionResponse};
+use problem::{
+    failure_response, retry_after_secs, Failure, InvalidParam, ProblemDetails, ProblemJson,
+};
+use search::{normalize_query, NameIndex, SearchResponse};
+use sort::{sort_pokemon, DetailIndex, NotReady, SortKey, SortOrder};
+use species::{Species, SpeciesResponse};
+use types::{
+    TypeDetail, TypeDetailResponse, TypeListResponse, TypeMatrix, TypeMatrixResponse, TypeSummary,
+};
+
+pub(crate) struct Api {
+    /// Url the API is reachable at, used to build pagination links
+    public_url: Url,
+    cursors: CursorCodec,
+    names: NameIndex,
+    details: DetailIndex,
+}
+
+/// Which query parameters pagination links carry, following the style of the request.
+#[derive(Debug, Clone, Copy)]
+enum PageStyle {
+    Offset,
+    Cursor,
+}
+
+/// One page of the Pokémon index, before conversion to the response.
+struct IndexPage {
+    results: Vec<NamedApiResource>,
+    total: u32,
+    next: Option<Cursor>,
+    previous: Option<Cursor>,
+}
+
+impl IndexPage {
+    /// Cuts a page out of an index already filtered and sorted here.
+    fn slice(mut all: Vec<NamedApiResource>, page: Cursor) -> Self {
+        let total = all.len() as u32;
+        let start = page.offset.min(total);
+        let end = start.saturating_add(page.limit).min(total);
+        Self {
+            next: (end < total).then_some(Cursor {
+                offset: end,
+                limit: page.limit,
+            }),
+            previous: (start > 0).then_some(Cursor {
+                offset: start.saturating_sub(page.limit),
+                limit: page.limit,
+            }),
+            results: all.drain(start as usize..end as usize).collect(),
+            total,
+        }
+    }
+}
+
+/// Filtering and sorting parameters of a list request, carried over to its pagination links.
+struct ListQuery {
+    filter: PokemonFilter,
+    sort: Option<SortKey>,
+    order: Option<SortOrder>,
+}
+
+impl ListQuery {
+    fn pairs(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("type", self.filter.type_.as_deref()),
+            ("generation", self.filter.generation.as_deref()),
+            ("ability", self.filter.ability.as_deref()),
+            ("habitat", self.filter.habitat.as_deref()),
+            ("sort", self.sort.map(SortKey::as_str)),
+            ("order", self.order.map(SortOrder::as_str)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+    }
+
+    /// Whether the upstream order and pagination can be used as they are.
+    fn is_plain(&self) -> bool {
+        self.filter.is_empty()
+            && self.sort.unwrap_or(SortKey::Id) == SortKey::Id
+            && self.order.unwrap_or(SortOrder::Asc) == SortOrder::Asc
+    }
+}
+
+impl Api {
+    pub fn new(public_url: Url, cursor_key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            public_url,
+            cursors: CursorCodec::new(cursor_key),
+            names: NameIndex::new(),
+            details: DetailIndex::new(),
+        }
+    }
+
+    fn page_url(&self, page: Cursor, style: PageStyle, query: &ListQuery) -> String {
+        let mut url = self.public_url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().push("pokemon");
+        }
+        url.query_pairs_mut().extend_pairs(query.pairs());
+        match style {
+            PageStyle::Offset => url
+                .query_pairs_mut()
+                .append_pair("limit", &page.limit.to_string())
+                .append_pair("offset", &page.offset.to_string()),
+            PageStyle::Cursor => url
+                .query_pairs_mut()
+                .append_pair("cursor", &self.cursors.encode(page)),
+        };
+        url.into()
+    }
+
+    /// Extracts the page an upstream `next`/`previous` link points at.
+    fn upstream_page(upstream: &str) -> Option<Cursor> {
+        let url = Url::parse(upstream).ok()?;
+        let (mut limit, mut offset) = (None, 0);
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "limit" => limit = value.parse().ok(),
+                "offset" => offset = value.parse().ok()?,
+                _ => {}
+            }
+        }
+        Some(Cursor {
+            offset,
+            limit: limit?,
+        })
+    }
+
+    /// RFC 8288 `Link` header with first, last, next and previous pages.
+    fn link_header(&self, page: &PokemonPage, style: PageStyle, query: &ListQuery) -> String {
+        let first = Cursor {
+            offset: 0,
+            limit: page.limit,
+        };
+        let mut links = vec![format!(
+            "<{}>; rel=\"first\"",
+            self.page_url(first, style, query)
+        )];
+        if let Some(previous) = &page.previous {
+            links.push(format!("<{previous}>; rel=\"prev\""));
+        }
+        if let Some(next) = &page.next {
+            links.push(format!("<{next}>; rel=\"next\""));
+        }
+        if page.limit > 0 && page.total > 0 {
+            let last = Cursor {
+                offset: (page.total - 1) / page.limit * page.limit,
+                limit: page.limit,
+            };
+            links.push(format!(
+                "<{}>; rel=\"last\"",
+                self.page_url(last, style, query)
+            ));
+        }
+        links.join(", ")
+    }
+}
+
+failure_response! {
+    #[derive(ApiResponse)]
+    #[oai(bad_request_handler = "list_bad_request")]
+    enum PokemonListResponse {
+        #[oai(status = 200)]
+        Ok(
+            Json<PokemonPage>,
+            #[oai(header = "Link")] String,
+            #[oai(header = "X-Total-Count")] u32,
+        ),
+        /// A query parameter is out of bounds, a filter names an unknown value, or the cursor is
+        /// malformed or was not issued by this server
+        #[oai(status = 400)]
+        BadRequest(ProblemJson),
+    }
+}
+
+fn list_bad_request(err: poem::Error) -> PokemonListResponse {
+    PokemonListResponse::BadRequest(ProblemDetails::from_bad_request(err))
+}
+
+/// Normalizes a filter query parameter; an empty one, as forms send for blank fields, is
+/// no filter.
+fn filter_value(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty()).map(|v| v.to_lowercase())
+}
+
+#[derive(Serialize, Object)]
+struct Pokemon {
+    pub id: u32,
+    pub name: String,
+}
+
+impl Pokemon {
+    fn new(r: NamedApiResource, base: &Url) -> Result<Self, PokedexError> {
+        Ok(Pokemon {
+            id: r.id(base, "pokemon")?,
+            name: r.name,
+        })
+    }
+}
+
+#[derive(Serialize, Object)]
+struct PokemonPage {
+    pub items: Vec<Pokemon>,
+    /// Number of Pokémon across all pages
+    pub total: u32,
+    pub limit: u32,
+    pub offset: u32,
+    /// Url of the next page, if any
+    pub next: Option<String>,
+    /// Url of the previous page, if any
+    pub previous: Option<String>,
+    /// Opaque cursor of the next page, if any
+    pub next_cursor: Option<String>,
+    /// Opaque cursor of the previous page, if any
+    pub previous_cursor: Option<String>,
+}
+
+failure_response! {
+    not_found: NotFound;
+    #[derive(ApiResponse)]
+    enum PokemonDetailResponse {
+        #[oai(status = 200)]
+        Ok(Json<PokemonDetail>),
+        /// No Pokémon with this id or name
+        #[oai(status = 404)]
+        NotFound(ProblemJson),
+    }
+}
+
+#[derive(Serialize, Object)]
+struct PokemonDetail {
+    pub id: u32,
+    pub name: String,
+    /// Height in decimetres
+    pub height: u32,
+    /// Weight in hectograms
+    pub weight: u32,
+    pub base_experience: Option<u32>,
+    /// Type names ordered by slot
+    pub types: Vec<String>,
+    pub abilities: Vec<PokemonAbility>,
+    pub stats: Vec<PokemonStat>,
+    pub sprites: PokemonSprites,
+}
+
+#[derive(Serialize, Object)]
+struct PokemonAbility {
+    pub name: String,
+    pub slot: u32,
+    pub is_hidden: bool,
+}
+
+#[derive(Serialize, Object)]
+struct PokemonStat {
+    pub name: String,
+    pub base_stat: u32,
+    pub effort: u32,
+}
+
+#[derive(Serialize, Object)]
+struct PokemonSprites {
+    pub front_default: Option<String>,
+    pub front_shiny: Option<String>,
+    pub back_default: Option<String>,
+    pub back_shiny: Option<String>,
+}
+
+#[derive(Serialize, Enum)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+enum HealthStatus {
+    Ok,
+    Degraded,
+}
+
+#[derive(Serialize, Enum)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+enum CircuitBreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Serialize, Object)]
+struct Health {
+    pub status: HealthStatus,
+    pub circuit_breaker: Option<CircuitBreakerHealth>,
+}
+
+#[derive(Serialize, Object)]
+struct CircuitBreakerHealth {
+    pub state: CircuitBreakerState,
+    pub consecutive_failures: u32,
+    /// Seconds until upstream is probed again, while the circuit is open
+    pub retry_after: Option<u64>,
+}
+
+impl From<CircuitState> for CircuitBreakerState {
+    fn from(state: CircuitState) -> Self {
+        match state {
+            CircuitState::Closed => Self::Closed,
+            CircuitState::Open => Self::Open,
+            CircuitState::HalfOpen => Self::HalfOpen,
+        }
+    }
+}
+
+impl From<CircuitBreakerStatus> for CircuitBreakerHealth {
+    fn from(status: CircuitBreakerStatus) -> Self {
+        Self {
+            state: status.state.into(),
+            consecutive_failures: status.consecutive_failures,
+            retry_after: status.retry_after.map(retry_after_secs),
+        }
+    }
+}
+
+impl From<pokemon_api::PokemonDetail> for PokemonDetail {
+    fn from(mut p: pokemon_api::PokemonDetail) -> Self {
+        p.types.sort_by_key(|t| t.slot);
+        p.abilities.sort_by_key(|a| a.slot);
+        Self {
+            id: p.id,
+            name: p.name,
+            height: p.height,
+            weight: p.weight,
+            base_experience: p.base_experience,
+            types: p.types.drain(..).map(|t| t.type_.name).collect(),
+            abilities: p
+                .abilities
+                .drain(..)
+                .map(|a| PokemonAbility {
+                    name: a.ability.name,
+                    slot: a.slot,
+                    is_hidden: a.is_hidden,
+                })
+                .collect(),
+            stats: p
+                .stats
+                .drain(..)
+                .map(|s| PokemonStat {
+                    name: s.stat.name,
+                    base_stat: s.base_stat,
+                    effort: s.effort,
+                })
+                .collect(),
+            sprites: PokemonSprites {
+                front_default: p.sprites.front_default,
+                front_shiny: p.sprites.front_shiny,
+                back_default: p.sprites.back_default,
+                back_shiny: p.sprites.back_shiny,
+            },
+        }
+    }
+}
+
+#[OpenApi]
+impl Api {
+    #[oai(path = "/pokemon", method = "get")]
+    // One argument per query parameter, as poem-openapi extracts them
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn pokemon(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+        #[oai(validator(minimum(value = "1"), maximum(value = "100")))] Query(limit): Query<
+            Option<u32>,
+        >,
+        #[oai(validator(maximum(value = "100000")))] Query(offset): Query<Option<u32>>,
+        Query(cursor): Query<Option<String>>,
+        /// Only Pokémon of this type, e.g. "fire"
+        #[oai(name = "type")]
+        Query(type_): Query<Option<String>>,
+        /// Only Pokémon whose species was introduced in this generation, by id or name
+        Query(generation): Query<Option<String>>,
+        /// Only Pokémon that can have this ability, e.g. "levitate"
+        Query(ability): Query<Option<String>>,
+        /// Only Pokémon whose species lives in this habitat, e.g. "cave"
+        Query(habitat): Query<Option<String>>,
+        /// Defaults to id
+        Query(sort): Query<Option<SortKey>>,
+        /// Defaults to asc
+        Query(order): Query<Option<SortOrder>>,
+    ) -> PokemonListResponse {
+        // A cursor from a previous page takes precedence over `limit` and `offset`
+        let (Cursor { offset, limit }, style) = match cursor {
+            Some(token) => match self.cursors.decode(&token) {
+                Some(cursor) => (cursor, PageStyle::Cursor),
+                None => {
+                    return PokemonListResponse::BadRequest(ProblemDetails::invalid_params(
+                        vec![InvalidParam {
+                            name: "cursor".to_owned(),
+                            reason: "malformed or not issued by this server".to_owned(),
+                        }],
+                        Some(req),
+                    ))
+                }
+            },
+            None => (
+                Cursor {
+                    offset: offset.unwrap_or(0),
+                    limit: limit.unwrap_or(20),
+                },
+                PageStyle::Offset,
+            ),
+        };
+        let query = ListQuery {
+            filter: PokemonFilter {
+                type_: filter_value(type_),
+                generation: filter_value(generation),
+                ability: filter_value(ability),
+                habitat: filter_value(habitat),
+            },
+            sort,
+            order,
+        };
+        let (sort, order) = (sort.unwrap_or(SortKey::Id), order.unwrap_or(SortOrder::Asc));
+        let details = if sort.needs_details() {
+            match self.details.get_or_build(pokedex) {
+                Ok(details) => Some(details),
+                Err(not_ready) => {
+                    let retry_after = Some(retry_after_secs(not_ready.retry_after()));
+                    let detail = match not_ready {
+                        NotReady::Building => "are still being loaded",
+                        NotReady::Failed { .. } => "could not be loaded",
+                    };
+                    let failure = Failure::ServiceUnavailable { retry_after };
+                    return PokemonListResponse::ServiceUnavailable(
+                        failure.problem(
+                            format!(
+                                "Pokémon details needed to sort by {} {detail}",
+                                sort.as_str()
+                            ),
+                            req,
+                        ),
+                        retry_after,
+                    );
+                }
+            }
+        } else {
+            None
+        };
+        let result = if query.is_plain() {
+            pokedex
+                .list_pokemon(limit, offset)
+                .await
+                .map(|r| IndexPage {
+                    next: r.next.as_deref().and_then(Self::upstream_page),
+                    previous: r.previous.as_deref().and_then(Self::upstream_page),
+                    total: r.count,
+                    results: r.results,
+                })
+        } else {
+            async {
+                let all = if query.filter.is_empty() {
+                    pokedex.pokemon_index().await?
+                } else {
+                    pokedex.filter_pokemon(&query.filter).await?
+                };
+                let base = pokedex.base_url();
+                let sorted = sort_pokemon(all, base, sort, order, details.as_deref())?;
+                Ok::<_, PokedexError>(IndexPage::slice(sorted, Cursor { offset, limit }))
+            }
+            .await
+        };
+        match result {
+            Ok(r) => {
+                let result: Result<Vec<Pokemon>, _> = r
+                    .results
+                    .into_iter()
+                    .map(|p| Pokemon::new(p, pokedex.base_url()))
+                    .collect();
+                match result {
+                    Ok(items) => {
+                        let page = PokemonPage {
+                            items,
+                            total: r.total,
+                            limit,
+                            offset,
+                            next: r.next.map(|c| self.page_url(c, style, &query)),
+                            previous: r.previous.map(|c| self.page_url(c, style, &query)),
+                            next_cursor: r.next.map(|c| self.cursors.encode(c)),
+                            previous_cursor: r.previous.map(|c| self.cursors.encode(c)),
+                        };
+                        let link = self.link_header(&page, style, &query);
+                        PokemonListResponse::Ok(Json(page), link, r.total)
+                    }
+                    Err(e) => PokemonListResponse::from_error(e, req),
+                }
+            }
+            Err(PokedexError::UnknownFilterValue { filter, value }) => {
+                PokemonListResponse::BadRequest(ProblemDetails::invalid_params(
+                    vec![InvalidParam {
+                        name: filter.to_owned(),
+                        reason: format!("no {filter} named {value:?}"),
+                    }],
+                    Some(req),
+                ))
+            }
+            Err(e) => PokemonListResponse::from_error(e, req),
+        }
+    }
+
+    /// Pokémon whose name matches the query exactly, by prefix, by substring or
+    /// within a few typos, best matches first
+    #[oai(path = "/pokemon/search", method = "get")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn search(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+        #[oai(validator(min_length = 1, max_length = 100))] Query(q): Query<String>,
+        #[oai(validator(minimum(value = "1"), maximum(value = "50")))] Query(limit): Query<
+            Option<u32>,
+        >,
+    ) -> SearchResponse {
+        let query = normalize_query(&q);
+        if query.is_empty() {
+            return SearchResponse::BadRequest(ProblemDetails::invalid_params(
+                vec![InvalidParam {
+                    name: "q".to_owned(),
+                    reason: "must not be blank".to_owned(),
+                }],
+                Some(req),
+            ));
+        }
+        let limit = limit.unwrap_or(10) as usize;
+        match self.names.search(pokedex.as_ref(), &query, limit).await {
+            Ok(matches) => SearchResponse::Ok(Json(matches)),
+            Err(e) => SearchResponse::from_error(e, req),
+        }
+    }
+
+    #[oai(path = "/pokemon/:id_or_name", method = "get")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn pokemon_detail(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+        Path(id_or_name): Path<String>,
+    ) -> PokemonDetailResponse {
+        let result = pokedex.find_pokemon(&id_or_name).await;
+        match result {
+            Ok(p) => PokemonDetailResponse::Ok(Json(p.into())),
+            Err(e) => PokemonDetailResponse::from_error(e, req),
+        }
+    }
+
+    /// Details of several Pokémon at once, in the order requested; each item reports
+    /// its own failure without failing the others
+    #[oai(path = "/pokemon/batch", method = "post")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn pokemon_batch(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+        Json(batch): Json<BatchRequest>,
+    ) -> BatchResponse {
+        let blank: Vec<_> = batch
+            .ids
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| id.trim().is_empty())
+            .map(|(i, _)| InvalidParam {
+                name: format!("ids[{i}]"),
+                reason: "must not be blank".to_owned(),
+            })
+            .collect();
+        if !blank.is_empty() {
+            return BatchResponse::BadRequest(ProblemDetails::invalid_params(blank, Some(req)));
+        }
+        let results = pokedex.get_pokemon_batch(&batch.ids).await;
+        let items = batch
+            .ids
+            .into_iter()
+            .zip(results)
+            .map(|(query, result)| BatchItem::new(query, result, req))
+            .collect();
+        BatchResponse::Ok(Json(items))
+    }
+
+    #[oai(path = "/pokemon/:id_or_name/evolutions", method = "get")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn evolutions(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+        Path(id_or_name): Path<String>,
+    ) -> EvolutionResponse {
+        let result = async {
+            let pokemon = pokedex.find_pokemon(&id_or_name).await?;
+            let chain = pokedex.get_evolution_chain_of(&pokemon).await?;
+            EvolutionNode::new(chain.chain, pokedex.base_url())
+        }
+        .await;
+        match result {
+            Ok(tree) => EvolutionResponse::Ok(Json(tree)),
+            Err(e) => EvolutionResponse::from_error(e, req),
+        }
+    }
+
+    #[oai(path = "/species/:id", method = "get")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn species(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+        Path(id): Path<u32>,
+        Query(lang): Query<Option<String>>,
+    ) -> SpeciesResponse {
+        match pokedex.get_species(id).await {
+            Ok(s) => SpeciesResponse::Ok(Json(Species::new(s, lang.as_deref().unwrap_or("en")))),
+            Err(e) => SpeciesResponse::from_error(e, req),
+        }
+    }
+
+    #[oai(path = "/types", method = "get")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn types(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+    ) -> TypeListResponse {
+        let result = async {
+            let list = pokedex.list_types().await?;
+            list.results
+                .into_iter()
+                .map(|t| TypeSummary::new(t, pokedex.base_url()))
+                .collect::<Result<Vec<_>, _>>()
+        }
+        .await;
+        match result {
+            Ok(types) => TypeListResponse::Ok(Json(types)),
+            Err(e) => TypeListResponse::from_error(e, req),
+        }
+    }
+
+    /// Damage multipliers of every attacking type against every defending type
+    #[oai(path = "/types/matrix", method = "get")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn type_matrix(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+    ) -> TypeMatrixResponse {
+        match pokedex.get_all_types().await {
+            Ok(types) => TypeMatrixResponse::Ok(Json(TypeMatrix::new(types))),
+            Err(e) => TypeMatrixResponse::from_error(e, req),
+        }
+    }
+
+    #[oai(path = "/types/:id_or_name", method = "get")]
+    #[tracing::instrument(level=tracing::Level::INFO,skip(self, req, pokedex,))]
+    async fn type_detail(
+        &self,
+        req: &Request,
+        Data(pokedex): Data<&Arc<dyn PokedexBackend>>,
+        Path(id_or_name): Path<String>,
+    ) -> TypeDetailResponse {
+        let result = async {
+            let t = pokedex.get_type(&id_or_name.to_lowercase()).await?;
+            TypeDetail::new(t, pokedex.base_url())
+        }
+        .await;
+        match result {
+            Ok(t) => TypeDetailResponse::Ok(Json(t)),
+            Err(e) => TypeDetailResponse::from_error(e, req),
+        }
+    }
+
+    #[oai(path = "/health", method = "get")]
+    async fn health(&self, Data(pokedex): Data<&Arc<dyn PokedexBackend>>) -> Json<Health> {
+        let circuit_breaker = pokedex.status().circuit_breaker;
+        let status = match circuit_breaker {
+            Some(breaker) if breaker.state != CircuitState::Closed => HealthStatus::Degraded,
+            _ => HealthStatus::Ok,
+        };
+        Json(Health {
+            status,
+            circuit_breaker: circuit_breaker.map(Into::into),
+        })
+    }
+
+    /// Backend metrics in the Prometheus text exposition format
+    #[oai(path = "/metrics", method = "get")]
+    async fn metrics(&self, Data(pokedex): Data<&Arc<dyn PokedexBackend>>) -> PlainText<String> {
+        let status = pokedex.status();
+        let mut out = String::new();
+        if let Some(breaker) = status.circuit_breaker {
+            out.push_str("# TYPE pokedex_circuit_breaker_state gauge\n");
+            for (state, label) in [
+                (CircuitState::Closed, "closed"),
+                (CircuitState::Open, "open"),
+                (CircuitState::HalfOpen, "half_open"),
+            ] {
+                let value = u8::from(breaker.state == state);
+                let _ = writeln!(
+                    out,
+                    "pokedex_circuit_breaker_state{{state=\"{label}\"}} {value}"
+                );
+            }
+            out.push_str("# TYPE pokedex_circuit_breaker_consecutive_failures gauge\n");
+            let _ = writeln!(
+                out,
+                "pokedex_circuit_breaker_consecutive_failures {}",
+                breaker.consecutive_failures
+            );
+        }
+        if let Some(cache) = status.memory_cache {
+            out.push_str("# TYPE pokedex_memory_cache_hits_total counter\n");
+            let _ = writeln!(out, "pokedex_memory_cache_hits_total {}", cache.hits);
+            out.push_str("# TYPE pokedex_memory_cache_misses_total counter\n");
+            let _ = writeln!(out, "pokedex_memory_cache_misses_total {}", cache.misses);
+            out.push_str("# TYPE pokedex_memory_cache_entries gauge\n");
+            let _ = writeln!(out, "pokedex_memory_cache_entries {}", cache.entries);
+        }
+        PlainText(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use poem::{
+        http::{Method, StatusCode},
+        Endpoint, EndpointExt, Response,
+    };
+    use poem_openapi::OpenApiService;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::pokemon_api::LocalPokedex;
+
+    /// Offline data dump with two Pokémon and no other resources.
+    struct Dump(PathBuf);
+
+    impl Dump {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("pokedex-api-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join("pokemon")).unwrap();
+            std::fs::write(
+                dir.join("pokemon/index.json"),
+                r#"{"count": 2, "next": null, "previous": null, "results": [
+                    {"name": "bulbasaur", "url": "/api/v2/pokemon/1/"},
+                    {"name": "ivysaur", "url": "/api/v2/pokemon/2/"}
+                ]}"#,
+            )
+            .unwrap();
+            Self(dir)
+        }
+
+        async fn call(&self, req: Request) -> Response {
+            let pokedex: Arc<dyn PokedexBackend> =
+                Arc::new(LocalPokedex::new(&self.0, "https://pokeapi.co/api/v2/").unwrap());
+            let api = Api::new("http://localhost:3001/api".parse().unwrap(), "test key");
+            let ep = OpenApiService::new(api, "Test", "1.0").data(pokedex);
+            ep.get_response(req).await
+        }
+
+        async fn get(&self, uri: &str) -> Response {
+            self.call(Request::builder().uri(uri.parse().unwrap()).finish())
+                .await
+        }
+
+        async fn post(&self, uri: &str, json: &'static str) -> Response {
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri(uri.parse().unwrap())
+                .content_type("application/json")
+                .body(json);
+            self.call(req).await
+        }
+    }
+
+    impl Drop for Dump {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Names of the invalid parameters reported by a 400 response.
+    async fn invalid_params(resp: Response) -> Vec<String> {
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = resp.into_body().into_json().await.unwrap();
+        body["invalid_params"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn empty_filter_values_are_ignored() {
+        let dump = Dump::new("empty-filters");
+        let resp = dump.get("/pokemon?type=&habitat=").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["X-Total-Count"], "2");
+    }
+
+    #[tokio::test]
+    async fn unknown_filter_values_are_bad_requests() {
+        let dump = Dump::new("unknown-filters");
+        for value in ["dragonfly", "..", "."] {
+            let resp = dump.get(&format!("/pokemon?type={value}")).await;
+            assert_eq!(invalid_params(resp).await, ["type"], "type={value}");
+        }
+    }
+
+    #[tokio::test]
+    async fn blank_batch_ids_are_bad_requests() {
+        let dump = Dump::new("blank-batch-ids");
+        let resp = dump
+            .post("/pokemon/batch", r#"{"ids": ["bulbasaur", "", " "]}"#)
+            .await;
+        assert_eq!(invalid_params(resp).await, ["ids[1]", "ids[2]"]);
+    }
+
+    #[tokio::test]
+    async fn bad_cursors_are_bad_requests() {
+        let dump = Dump::new("bad-cursor");
+        let forged = CursorCodec::new("another key").encode(Cursor {
+            offset: 0,
+            limit: 1,
+        });
+        for cursor in [forged.as_str(), "garbage"] {
+            let resp = dump.get(&format!("/pokemon?cursor={cursor}")).await;
+            assert_eq!(invalid_params(resp).await, ["cursor"], "cursor={cursor}");
+        }
+    }
+}
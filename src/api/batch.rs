@@ -0,0 +1,65 @@
+use poem::Request;
+use poem_openapi::payload::Json;
+use poem_openapi_derive::{ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    problem::{Failure, ProblemDetails, ProblemJson},
+    PokemonDetail,
+};
+use crate::pokemon_api::{self, PokedexError};
+
+#[derive(ApiResponse)]
+#[oai(bad_request_handler = "batch_bad_request")]
+pub(super) enum BatchResponse {
+    /// One item per requested Pokémon, in request order
+    #[oai(status = 200)]
+    Ok(Json<Vec<BatchItem>>),
+    /// The body is malformed, has no ids or more than 50, or has a blank one
+    #[oai(status = 400)]
+    BadRequest(ProblemJson),
+}
+
+fn batch_bad_request(err: poem::Error) -> BatchResponse {
+    BatchResponse::BadRequest(ProblemDetails::from_bad_request(err))
+}
+
+#[derive(Debug, Deserialize, Object)]
+pub(super) struct BatchRequest {
+    /// Ids or names of the Pokémon to look up
+    #[oai(validator(min_items = 1, max_items = 50))]
+    pub ids: Vec<String>,
+}
+
+/// Outcome of looking up one Pokémon; exactly one of `pokemon` and `error` is set.
+#[derive(Serialize, Object)]
+pub(super) struct BatchItem {
+    /// The id or name as requested
+    pub query: String,
+    pub pokemon: Option<PokemonDetail>,
+    pub error: Option<ProblemDetails>,
+}
+
+impl BatchItem {
+    pub fn new(
+        query: String,
+        result: Result<pokemon_api::PokemonDetail, PokedexError>,
+        req: &Request,
+    ) -> Self {
+        match result {
+            Ok(p) => Self {
+                query,
+                pokemon: Some(p.into()),
+                error: None,
+            },
+            Err(e) => {
+                let (_, ProblemJson(problem)) = Failure::from_error(e, req);
+                Self {
+                    query,
+                    pokemon: None,
+                    error: Some(problem),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use poem_openapi::payload::Json;
+use poem_openapi_derive::{ApiResponse, Enum, Object};
+use reqwest::Url;
+use serde::Serialize;
+
+use super::{
+    problem::{failure_response, ProblemJson},
+    Pokemon,
+};
+
+use crate::pokemon_api::{self, PokedexError};
+
+failure_response! {
+    not_found: NotFound;
+    #[derive(ApiResponse)]
+    pub(super) enum EvolutionResponse {
+        #[oai(status = 200)]
+        Ok(Json<EvolutionNode>),
+        /// No Pokémon with this id or name, or it has no evolution chain
+        #[oai(status = 404)]
+        NotFound(ProblemJson),
+    }
+}
+
+/// One stage of an evolution tree.
+#[derive(Serialize, Object)]
+pub(super) struct EvolutionNode {
+    /// The default Pokémon of this stage's species
+    pub pokemon: Pokemon,
+    pub is_baby: bool,
+    /// Alternative ways to evolve into this stage from its parent; empty for the root
+    pub triggers: Vec<EvolutionTrigger>,
+    pub evolves_to: Vec<EvolutionNode>,
+}
+
+#[derive(Serialize, Enum)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub(super) enum EvolutionTriggerKind {
+    LevelUp,
+    Trade,
+    UseItem,
+    Shed,
+    Other,
+}
+
+/// Conditions of one way to evolve; all given conditions must hold at once.
+#[derive(Serialize, Object)]
+pub(super) struct EvolutionTrigger {
+    pub kind: EvolutionTriggerKind,
+    /// Upstream name of the trigger, e.g. "level-up" or "spin"
+    pub trigger: String,
+    pub min_level: Option<u32>,
+    /// Item to use on the Pokémon
+    pub item: Option<String>,
+    /// Item the Pokémon must hold
+    pub held_item: Option<String>,
+    /// Minimum friendship
+    pub min_happiness: Option<u32>,
+    pub min_affection: Option<u32>,
+    pub min_beauty: Option<u32>,
+    /// "day" or "night"
+    pub time_of_day: Option<String>,
+    pub known_move: Option<String>,
+    pub known_move_type: Option<String>,
+    pub location: Option<String>,
+    /// Species the Pokémon must be traded for
+    pub trade_species: Option<String>,
+    /// 1 for female, 2 for male
+    pub gender: Option<u32>,
+    pub needs_overworld_rain: bool,
+    pub turn_upside_down: bool,
+}
+
+impl EvolutionNode {
+    pub fn new(link: pokemon_api::ChainLink, base: &Url) -> Result<Self, PokedexError> {
+        let id = link.species.id(base, "pokemon-species")?;
+        Ok(Self {
+            pokemon: Pokemon {
+                id,
+                name: link.species.name,
+            },
+            is_baby: link.is_baby,
+            triggers: link
+                .evolution_details
+                .into_iter()
+                .map(EvolutionTrigger::from)
+                .collect(),
+            evolves_to: link
+                .evolves_to
+                .into_iter()
+                .map(|l| EvolutionNode::new(l, base))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<pokemon_api::EvolutionDetail> for EvolutionTrigger {
+    fn from(d: pokemon_api::EvolutionDetail) -> Self {
+        let kind = match d.trigger.name.as_str() {
+            "level-up" => EvolutionTriggerKind::LevelUp,
+            "trade" => EvolutionTriggerKind::Trade,
+            "use-item" => EvolutionTriggerKind::UseItem,
+            "shed" => EvolutionTriggerKind::Shed,
+            _ => EvolutionTriggerKind::Other,
+        };
+        Self {
+            kind,
+            trigger: d.trigger.name,
+            min_level: d.min_level,
+            item: d.item.map(|r| r.name),
+            held_item: d.held_item.map(|r| r.name),
+            min_happiness: d.min_happiness,
+            min_affection: d.min_affection,
+            min_beauty: d.min_beauty,
+            time_of_day: Some(d.time_of_day).filter(|t| !t.is_empty()),
+            known_move: d.known_move.map(|r| r.name),
+            known_move_type: d.known_move_type.map(|r| r.name),
+            location: d.location.map(|r| r.name),
+            trade_species: d.trade_species.map(|r| r.name),
+            gender: d.gender,
+            needs_overworld_rain: d.needs_overworld_rain,
+            turn_upside_down: d.turn_upside_down,
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::time::Duration;
+
+use poem::{
+    http::{header, HeaderValue, StatusCode},
+    IntoResponse, Request, Response,
+};
+use poem_openapi::{
+    error::ParseParamError,
+    payload::Payload,
+    registry::{MetaSchemaRef, Registry},
+    types::{ToJSON, Type},
+};
+use poem_openapi_derive::Object;
+use serde::Serialize;
+use tracing::{error, warn, Span};
+
+use crate::pokemon_api::PokedexError;
+
+const PROBLEM_TYPE_BASE: &str = "urn:pokedex:problem:";
+
+/// Error body following RFC 7807, returned by every error response of the API.
+#[derive(Serialize, Object)]
+pub(super) struct ProblemDetails {
+    /// URI identifying the kind of problem
+    #[serde(rename = "type")]
+    #[oai(rename = "type")]
+    pub type_: String,
+    /// Short summary of the problem kind
+    pub title: String,
+    /// HTTP status code of the response
+    pub status: u16,
+    /// Explanation specific to this occurrence
+    pub detail: Option<String>,
+    /// Path of the request that failed
+    pub instance: Option<String>,
+    /// Process-local id of the request's tracing span, for correlating with server logs;
+    /// absent when the span is disabled
+    pub span_id: Option<String>,
+    /// Request parameters that failed validation
+    pub invalid_params: Option<Vec<InvalidParam>>,
+}
+
+#[derive(Serialize, Object)]
+pub(super) struct InvalidParam {
+    pub name: String,
+    pub reason: String,
+}
+
+impl ProblemDetails {
+    /// Response for a problem that occurred while handling `req`.
+    pub fn response(
+        status: StatusCode,
+        kind: &str,
+        detail: impl Into<String>,
+        req: &Request,
+    ) -> ProblemJson {
+        ProblemJson(Self::build(
+            status,
+            kind,
+            detail.into(),
+            Some(req.original_uri().path().to_owned()),
+        ))
+    }
+
+    /// 400 response listing the parameters that failed to parse or validate.
+    pub fn invalid_params(params: Vec<InvalidParam>, instance: Option<&Request>) -> ProblemJson {
+        let mut problem = Self::build(
+            StatusCode::BAD_REQUEST,
+            "invalid-params",
+            "One or more request parameters are invalid".to_owned(),
+            instance.map(|req| req.original_uri().path().to_owned()),
+        );
+        problem.invalid_params = Some(params);
+        ProblemJson(problem)
+    }
+
+    /// Converts an error raised while extracting request parameters.
+    pub fn from_bad_request(err: poem::Error) -> ProblemJson {
+        let param = match err.downcast_ref::<ParseParamError>() {
+            Some(e) => InvalidParam {
+                name: e.name.to_owned(),
+                reason: e.reason.clone(),
+            },
+            None => InvalidParam {
+                name: String::new(),
+                reason: err.to_string(),
+            },
+        };
+        Self::invalid_params(vec![param], None)
+    }
+
+    fn build(status: StatusCode, kind: &str, detail: String, instance: Option<String>) -> Self {
+        Self {
+            type_: format!("{PROBLEM_TYPE_BASE}{kind}"),
+            title: status.canonical_reason().unwrap_or_default().to_owned(),
+            status: status.as_u16(),
+            detail: Some(detail),
+            instance,
+            span_id: Span::current()
+                .id()
+                .map(|id| format!("{:016x}", id.into_u64())),
+            invalid_params: None,
+        }
+    }
+}
+
+/// [`ProblemDetails`] body served as `application/problem+json`, as RFC 7807 requires.
+pub(super) struct ProblemJson(pub ProblemDetails);
+
+impl Payload for ProblemJson {
+    const CONTENT_TYPE: &'static str = "application/problem+json";
+
+    fn schema_ref() -> MetaSchemaRef {
+        ProblemDetails::schema_ref()
+    }
+
+    fn register(registry: &mut Registry) {
+        ProblemDetails::register(registry);
+    }
+}
+
+impl IntoResponse for ProblemJson {
+    fn into_response(self) -> Response {
+        let mut resp = poem::web::Json(self.0.to_json()).into_response();
+        resp.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(Self::CONTENT_TYPE),
+        );
+        resp
+    }
+}
+
+/// How a [`PokedexError`] is reported to clients.
+pub(super) enum Failure {
+    NotFound,
+    BadGateway,
+    GatewayTimeout,
+    ServiceUnavailable { retry_after: Option<u64> },
+    Internal,
+}
+
+impl Failure {
+    pub fn from_error(e: PokedexError, req: &Request) -> (Self, ProblemJson) {
+        let failure = Self::classify(&e);
+        match failure {
+            Failure::NotFound => {}
+            Failure::ServiceUnavailable { .. } => warn!(err = %e),
+            _ => error!(err = %e),
+        }
+        let problem = failure.problem(failure.default_detail(), req);
+        (failure, problem)
+    }
+
+    fn classify(e: &PokedexError) -> Self {
+        match e {
+            PokedexError::NotFound | PokedexError::UnknownFilterValue { .. } => Failure::NotFound,
+            PokedexError::CircuitOpen { retry_after } => Failure::ServiceUnavailable {
+                retry_after: Some(retry_after_secs(*retry_after)),
+            },
+            PokedexError::HttpRequestError(e) if e.is_timeout() => Failure::GatewayTimeout,
+            PokedexError::HttpRequestError(_)
+            | PokedexError::InvalidResponseBody(_)
+            | PokedexError::InvalidResourceUrl(_) => Failure::BadGateway,
+            PokedexError::UnexpectedStatus {
+                status,
+                retry_after,
+            } => match *status {
+                StatusCode::SERVICE_UNAVAILABLE | StatusCode::TOO_MANY_REQUESTS => {
+                    Failure::ServiceUnavailable {
+                        retry_after: retry_after.map(retry_after_secs),
+                    }
+                }
+                StatusCode::GATEWAY_TIMEOUT => Failure::GatewayTimeout,
+                _ => Failure::BadGateway,
+            },
+            PokedexError::InvalidBaseUrl | PokedexError::LocalDataError(_) => Failure::Internal,
+            PokedexError::Coalesced(inner) => Self::classify(inner),
+        }
+    }
+
+    pub fn problem(&self, detail: impl Into<String>, req: &Request) -> ProblemJson {
+        let (status, kind) = match self {
+            Failure::NotFound => (StatusCode::NOT_FOUND, "not-found"),
+            Failure::BadGateway => (StatusCode::BAD_GATEWAY, "upstream-failed"),
+            Failure::GatewayTimeout => (StatusCode::GATEWAY_TIMEOUT, "upstream-timeout"),
+            Failure::ServiceUnavailable { .. } => {
+                (StatusCode::SERVICE_UNAVAILABLE, "upstream-unavailable")
+            }
+            Failure::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+        };
+        ProblemDetails::response(status, kind, detail, req)
+    }
+
+    fn default_detail(&self) -> &'static str {
+        match self {
+            Failure::NotFound => "Resource not found",
+            Failure::BadGateway => "Upstream PokeAPI failed or returned an invalid response",
+            Failure::GatewayTimeout => "Upstream PokeAPI did not respond in time",
+            Failure::ServiceUnavailable { .. } => "Upstream PokeAPI is unavailable",
+            Failure::Internal => "Internal server error",
+        }
+    }
+}
+
+/// Declares an API response enum with the upstream failure variants every endpoint shares,
+/// 500, 502, 503 with Retry-After and 504, appended to the given ones, and a `from_error`
+/// that reports a [`PokedexError`] through them.
+///
+/// `not_found: Variant;` names the variant a missing resource is reported with. Without
+/// it a missing resource is reported as 502, for endpoints whose resources always exist
+/// so that a missing one means upstream misbehaves.
+macro_rules! failure_response {
+    (
+        not_found: $not_found:ident;
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident { $($variants:tt)* }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variants)*
+            #[oai(status = 500)]
+            InternalServerError($crate::api::problem::ProblemJson),
+            /// Upstream PokeAPI failed or returned an invalid response
+            #[oai(status = 502)]
+            BadGateway($crate::api::problem::ProblemJson),
+            /// Upstream PokeAPI is unavailable, retry later
+            #[oai(status = 503)]
+            ServiceUnavailable(
+                $crate::api::problem::ProblemJson,
+                #[oai(header = "Retry-After")] Option<u64>,
+            ),
+            /// Upstream PokeAPI did not respond in time
+            #[oai(status = 504)]
+            GatewayTimeout($crate::api::problem::ProblemJson),
+        }
+
+        impl $name {
+            $vis fn from_error(
+                e: $crate::pokemon_api::PokedexError,
+                req: &::poem::Request,
+            ) -> Self {
+                use $crate::api::problem::Failure;
+                let (failure, body) = Failure::from_error(e, req);
+                match failure {
+                    Failure::NotFound => Self::$not_found(body),
+                    Failure::BadGateway => Self::BadGateway(body),
+                    Failure::GatewayTimeout => Self::GatewayTimeout(body),
+                    Failure::ServiceUnavailable { retry_after } => {
+                        Self::ServiceUnavailable(body, retry_after)
+                    }
+                    Failure::Internal => Self::InternalServerError(body),
+                }
+            }
+        }
+    };
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($variants:tt)* }) => {
+        $crate::api::problem::failure_response! {
+            not_found: BadGateway;
+            $(#[$meta])*
+            $vis enum $name { $($variants)* }
+        }
+    };
+}
+pub(super) use failure_response;
+
+/// Rounds up so clients never come back before the circuit lets them through.
+pub(super) fn retry_after_secs(delay: Duration) -> u64 {
+    delay.as_secs() + u64::from(delay.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use poem_openapi::ApiResponse;
+
+    use super::*;
+
+    fn problem() -> ProblemJson {
+        ProblemJson(ProblemDetails::build(
+            StatusCode::NOT_FOUND,
+            "not-found",
+            "Resource not found".to_owned(),
+            Some("/api/pokemon/0".to_owned()),
+        ))
+    }
+
+    failure_response! {
+        not_found: NotFound;
+        #[derive(ApiResponse)]
+        enum DetailResponse {
+            #[oai(status = 404)]
+            NotFound(ProblemJson),
+        }
+    }
+
+    failure_response! {
+        #[derive(ApiResponse)]
+        enum ListResponse {
+            #[oai(status = 200)]
+            Ok,
+        }
+    }
+
+    fn request() -> Request {
+        Request::builder()
+            .uri(poem::http::Uri::from_static("/api/pokemon/0"))
+            .finish()
+    }
+
+    #[test]
+    fn missing_resources_are_reported_as_chosen() {
+        let req = request();
+        assert!(matches!(
+            DetailResponse::from_error(PokedexError::NotFound, &req),
+            DetailResponse::NotFound(_)
+        ));
+        assert!(matches!(
+            ListResponse::from_error(PokedexError::NotFound, &req),
+            ListResponse::BadGateway(_)
+        ));
+        assert_eq!(ListResponse::Ok.into_response().status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn upstream_failures_map_onto_shared_variants() {
+        let req = request();
+        let open = PokedexError::CircuitOpen {
+            retry_after: Duration::from_millis(1500),
+        };
+        assert!(matches!(
+            DetailResponse::from_error(open, &req),
+            DetailResponse::ServiceUnavailable(_, Some(2))
+        ));
+        let status = PokedexError::UnexpectedStatus {
+            status: StatusCode::GATEWAY_TIMEOUT,
+            retry_after: None,
+        };
+        assert!(matches!(
+            ListResponse::from_error(status, &req),
+            ListResponse::GatewayTimeout(_)
+        ));
+        assert!(matches!(
+            ListResponse::from_error(PokedexError::InvalidBaseUrl, &req),
+            ListResponse::InternalServerError(_)
+        ));
+    }
+
+    #[test]
+    fn problems_are_served_as_problem_json() {
+        let resp = DetailResponse::NotFound(problem()).into_response();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            resp.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+    }
+
+    #[test]
+    fn problems_are_documented_as_problem_json() {
+        let meta = DetailResponse::meta();
+        let content_types: Vec<_> = meta.responses[0]
+            .content
+            .iter()
+            .map(|c| c.content_type)
+            .collect();
+        assert_eq!(content_types, ["application/problem+json"]);
+    }
+
+    #[test]
+    fn problem_body_follows_rfc_7807() {
+        let ProblemJson(problem) = problem();
+        let body = serde_json::to_value(problem).unwrap();
+        assert_eq!(body["type"], "urn:pokedex:problem:not-found");
+        assert_eq!(body["title"], "Not Found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["instance"], "/api/pokemon/0");
+        // Outside of a request there is no span to report
+        assert!(body["span_id"].is_null());
+    }
+}
@@ -0,0 +1,222 @@
+use poem_openapi::payload::Json;
+use poem_openapi_derive::{ApiResponse, Enum, Object};
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use super::{
+    problem::{failure_response, ProblemDetails, ProblemJson},
+    Pokemon,
+};
+use crate::pokemon_api::{PokedexBackend, PokedexError};
+
+failure_response! {
+    #[derive(ApiResponse)]
+    #[oai(bad_request_handler = "search_bad_request")]
+    pub(super) enum SearchResponse {
+        /// Matches, best first
+        #[oai(status = 200)]
+        Ok(Json<Vec<SearchMatch>>),
+        /// The query is empty or too long, or the limit is out of bounds
+        #[oai(status = 400)]
+        BadRequest(ProblemJson),
+    }
+}
+
+fn search_bad_request(err: poem::Error) -> SearchResponse {
+    SearchResponse::BadRequest(ProblemDetails::from_bad_request(err))
+}
+
+/// How a name matched the query, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Enum)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub(super) enum MatchKind {
+    Exact,
+    Prefix,
+    Substring,
+    /// Within a few typos of the name or its beginning
+    Fuzzy,
+}
+
+#[derive(Serialize, Object)]
+pub(super) struct SearchMatch {
+    pub id: u32,
+    pub name: String,
+    #[serde(rename = "match")]
+    #[oai(rename = "match")]
+    pub kind: MatchKind,
+    /// Number of typos between the query and the name; 0 unless the match is fuzzy
+    pub distance: u32,
+}
+
+/// Names of all Pokémon, loaded on the first search and kept for the lifetime of the server.
+pub(super) struct NameIndex {
+    entries: OnceCell<Vec<Pokemon>>,
+}
+
+impl NameIndex {
+    pub fn new() -> Self {
+        Self {
+            entries: OnceCell::new(),
+        }
+    }
+
+    async fn entries(&self, pokedex: &dyn PokedexBackend) -> Result<&[Pokemon], PokedexError> {
+        let entries = self
+            .entries
+            .get_or_try_init(|| async {
+                pokedex
+                    .pokemon_index()
+                    .await?
+                    .into_iter()
+                    .map(|p| Pokemon::new(p, pokedex.base_url()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .await?;
+        Ok(entries)
+    }
+
+    /// Best `limit` matches of `query`, which must already be normalized like Pokémon names.
+    pub async fn search(
+        &self,
+        pokedex: &dyn PokedexBackend,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchMatch>, PokedexError> {
+        let entries = self.entries(pokedex).await?;
+        let mut matches: Vec<_> = entries
+            .iter()
+            .filter_map(|p| {
+                let (kind, distance) = score(query, &p.name)?;
+                Some(SearchMatch {
+                    id: p.id,
+                    name: p.name.clone(),
+                    kind,
+                    distance,
+                })
+            })
+            .collect();
+        matches.sort_by_key(|m| (m.kind, m.distance, m.name.len(), m.id));
+        matches.truncate(limit);
+        Ok(matches)
+    }
+}
+
+/// Lowercases the query and joins words with dashes, like upstream names ("mr-mime").
+pub(super) fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+fn score(query: &str, name: &str) -> Option<(MatchKind, u32)> {
+    if name == query {
+        return Some((MatchKind::Exact, 0));
+    }
+    if name.starts_with(query) {
+        return Some((MatchKind::Prefix, 0));
+    }
+    if name.contains(query) {
+        return Some((MatchKind::Substring, 0));
+    }
+    let query: Vec<char> = query.chars().collect();
+    // One typo per three characters, so that short queries do not match everything
+    let max_distance = (query.len() / 3).min(3);
+    if max_distance == 0 {
+        return None;
+    }
+    let name: Vec<char> = name.chars().collect();
+    // Comparing with the beginning of the name too catches misspelt prefixes, like "pikc"
+    let beginning = &name[..name.len().min(query.len())];
+    let distance = edit_distance(&query, &name).min(edit_distance(&query, beginning));
+    (distance <= max_distance).then_some((MatchKind::Fuzzy, distance as u32))
+}
+
+/// Levenshtein distance.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn normalizes_like_upstream_names() {
+        assert_eq!(normalize_query("  Mr  Mime "), "mr-mime");
+        assert_eq!(normalize_query("PIKACHU"), "pikachu");
+    }
+
+    #[test]
+    fn ranks_exact_before_prefix_before_substring_before_fuzzy() {
+        let scores = [
+            score("pikachu", "pikachu"),
+            score("pika", "pikachu"),
+            score("chu", "pikachu"),
+            score("pikc", "pikachu"),
+        ];
+        assert_eq!(
+            scores,
+            [
+                Some((MatchKind::Exact, 0)),
+                Some((MatchKind::Prefix, 0)),
+                Some((MatchKind::Substring, 0)),
+                Some((MatchKind::Fuzzy, 1)),
+            ]
+        );
+        assert!(scores.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn allows_a_typo_per_three_characters_up_to_three() {
+        let cases = [
+            // Too short for any typo
+            ("pk", "pikachu", None),
+            ("pxk", "pikachu", Some(1)),
+            ("pxx", "pikachu", None),
+            ("chamander", "charmander", Some(1)),
+            // The misspelt beginning of a name
+            ("pikc", "pikachu", Some(1)),
+            ("pxkc", "pikachu", None),
+            ("bulbasuar", "bulbasaur", Some(2)),
+            ("blastoize-maga", "blastoise-mega", Some(2)),
+            // Fourteen characters would allow four typos without the cap
+            ("blaztoize-magu", "blastoise-mega", None),
+        ];
+        for (query, name, distance) in cases {
+            let expected = distance.map(|d| (MatchKind::Fuzzy, d));
+            assert_eq!(score(query, name), expected, "{query} -> {name}");
+        }
+    }
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_and_substitutions() {
+        let cases = [
+            ("", "abc", 3),
+            ("abc", "abc", 0),
+            ("ab", "ba", 2),
+            ("kitten", "sitting", 3),
+            ("pokemon", "pokémon", 1),
+        ];
+        for (a, b, distance) in cases {
+            assert_eq!(edit_distance(&chars(a), &chars(b)), distance, "{a} -> {b}");
+            assert_eq!(edit_distance(&chars(b), &chars(a)), distance, "{b} -> {a}");
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::{stream, StreamExt};
+use poem_openapi_derive::Enum;
+use reqwest::Url;
+use tracing::{error, info, warn};
+
+use crate::pokemon_api::{NamedApiResource, PokedexBackend, PokedexError};
+
+/// Detail requests in flight while building the [`DetailIndex`].
+const BUILD_CONCURRENCY: usize = 16;
+
+/// Retry-After sent while the [`DetailIndex`] is being built.
+const BUILDING_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// How long after a failed build of the [`DetailIndex`] the next one may start.
+const REBUILD_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "snake_case")]
+pub(super) enum SortKey {
+    Id,
+    Name,
+    /// Sum of the six base stats
+    BaseStatTotal,
+    Height,
+    Weight,
+}
+
+impl SortKey {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Id => "id",
+            SortKey::Name => "name",
+            SortKey::BaseStatTotal => "base_stat_total",
+            SortKey::Height => "height",
+            SortKey::Weight => "weight",
+        }
+    }
+
+    /// Whether sorting by this key needs the details of every Pokémon.
+    pub fn needs_details(self) -> bool {
+        matches!(
+            self,
+            SortKey::BaseStatTotal | SortKey::Height | SortKey::Weight
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename_all = "snake_case")]
+pub(super) enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+/// Sortable figures from the details of one Pokémon.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Figures {
+    base_stat_total: u32,
+    height: u32,
+    weight: u32,
+}
+
+enum State {
+    Empty,
+    Building,
+    Failed {
+        at: Instant,
+    },
+    /// Built without the Pokémon in `missing`, which are fetched again once
+    /// [`REBUILD_BACKOFF`] has passed since `at`
+    Ready {
+        figures: Arc<HashMap<u32, Figures>>,
+        missing: Vec<u32>,
+        at: Instant,
+        refilling: bool,
+    },
+}
+
+/// Why the [`DetailIndex`] cannot be used yet.
+pub(super) enum NotReady {
+    Building,
+    /// The last build failed; the next one starts once `retry_after` has passed
+    Failed {
+        retry_after: Duration,
+    },
+}
+
+impl NotReady {
+    /// When asking again may find the index built.
+    pub fn retry_after(&self) -> Duration {
+        match self {
+            NotReady::Building => BUILDING_RETRY_AFTER,
+            NotReady::Failed { retry_after } => *retry_after,
+        }
+    }
+}
+
+/// Figures of every Pokémon by id.
+///
+/// Building it takes a detail request per Pokémon, so it is built in the background
+/// on first use and kept for the lifetime of the server. Pokémon whose details fail
+/// to load are left out at first and fetched again in the background after a backoff,
+/// until they all load; a build that fails altogether is retried after a backoff too.
+pub(super) struct DetailIndex {
+    state: Arc<Mutex<State>>,
+}
+
+impl DetailIndex {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State::Empty)),
+        }
+    }
+
+    /// Returns the index once built; until then starts building it, unless the last
+    /// build failed too recently, and reports why it is not ready.
+    pub fn get_or_build(
+        &self,
+        pokedex: &Arc<dyn PokedexBackend>,
+    ) -> Result<Arc<HashMap<u32, Figures>>, NotReady> {
+        let mut state = self.state.lock().expect("detail index lock poisoned");
+        match &mut *state {
+            State::Ready {
+                figures,
+                missing,
+                at,
+                refilling,
+            } => {
+                if !missing.is_empty() && !*refilling && at.elapsed() >= REBUILD_BACKOFF {
+                    *refilling = true;
+                    self.refill(pokedex, missing.clone());
+                }
+                return Ok(figures.clone());
+            }
+            State::Building => return Err(NotReady::Building),
+            State::Failed { at } => {
+                let retry_after = REBUILD_BACKOFF.saturating_sub(at.elapsed());
+                if !retry_after.is_zero() {
+                    return Err(NotReady::Failed { retry_after });
+                }
+            }
+            State::Empty => {}
+        }
+        *state = State::Building;
+        let (shared, pokedex) = (self.state.clone(), pokedex.clone());
+        tokio::spawn(async move {
+            let result = build(pokedex.as_ref()).await;
+            let mut state = shared.lock().expect("detail index lock poisoned");
+            *state = match result {
+                Ok(fetched) => {
+                    match &fetched.last_error {
+                        Some(e) => warn!(
+                            err = %e,
+                            entries = fetched.figures.len(),
+                            missing = fetched.missing.len(),
+                            backoff = ?REBUILD_BACKOFF,
+                            "detail index built without some Pokémon"
+                        ),
+                        None => info!(entries = fetched.figures.len(), "detail index built"),
+                    }
+                    State::Ready {
+                        figures: Arc::new(fetched.figures),
+                        missing: fetched.missing,
+                        at: Instant::now(),
+                        refilling: false,
+                    }
+                }
+                Err(e) => {
+                    error!(err = %e, backoff = ?REBUILD_BACKOFF, "building detail index failed");
+                    State::Failed { at: Instant::now() }
+                }
+            };
+        });
+        Err(NotReady::Building)
+    }
+
+    /// Fetches the figures of the Pokémon a build left out and adds those that load.
+    fn refill(&self, pokedex: &Arc<dyn PokedexBackend>, ids: Vec<u32>) {
+        let (shared, pokedex) = (self.state.clone(), pokedex.clone());
+        tokio::spawn(async move {
+            let fetched = fetch(pokedex.as_ref(), ids).await;
+            let mut state = shared.lock().expect("detail index lock poisoned");
+            let State::Ready {
+                figures,
+                missing,
+                at,
+                refilling,
+            } = &mut *state
+            else {
+                return;
+            };
+            let mut merged = HashMap::clone(figures);
+            merged.extend(fetched.figures);
+            match &fetched.last_error {
+                Some(e) => warn!(
+                    err = %e,
+                    entries = merged.len(),
+                    missing = fetched.missing.len(),
+                    "some Pokémon still missing from the detail index"
+                ),
+                None => info!(entries = merged.len(), "detail index completed"),
+            }
+            *figures = Arc::new(merged);
+            *missing = fetched.missing;
+            *at = Instant::now();
+            *refilling = false;
+        });
+    }
+}
+
+/// Figures fetched for a set of Pokémon.
+#[derive(Default)]
+struct Fetched {
+    figures: HashMap<u32, Figures>,
+    /// Pokémon whose details failed to load, to be fetched again
+    missing: Vec<u32>,
+    last_error: Option<PokedexError>,
+}
+
+/// Fetches the figures of every listed Pokémon; fails only if the list does, or if no
+/// details load at all.
+async fn build(pokedex: &dyn PokedexBackend) -> Result<Fetched, PokedexError> {
+    let ids = pokedex
+        .pokemon_index()
+        .await?
+        .iter()
+        .map(|p| p.id(pokedex.base_url(), "pokemon"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let fetched = fetch(pokedex, ids).await;
+    match fetched.last_error {
+        Some(e) if fetched.figures.is_empty() => Err(e),
+        _ => Ok(fetched),
+    }
+}
+
+async fn fetch(pokedex: &dyn PokedexBackend, ids: Vec<u32>) -> Fetched {
+    let mut fetched = Fetched::default();
+    let mut results = stream::iter(ids)
+        .map(|id| async move { (id, pokedex.get_pokemon_by_id(id).await) })
+        .buffer_unordered(BUILD_CONCURRENCY);
+    while let Some((id, result)) = results.next().await {
+        match result {
+            Ok(d) => {
+                let figures = Figures {
+                    base_stat_total: d.stats.iter().map(|s| s.base_stat).sum(),
+                    height: d.height,
+                    weight: d.weight,
+                };
+                fetched.figures.insert(id, figures);
+            }
+            // Listed but without details; sorts last
+            Err(PokedexError::NotFound) => {}
+            Err(e) => {
+                fetched.missing.push(id);
+                fetched.last_error = Some(e);
+            }
+        }
+    }
+    fetched
+}
+
+/// Sorts Pokémon by `key`, breaking ties by id.
+///
+/// Pokémon missing from `details` come last in either order.
+pub(super) fn sort_pokemon(
+    all: Vec<NamedApiResource>,
+    base: &Url,
+    key: SortKey,
+    order: SortOrder,
+    details: Option<&HashMap<u32, Figures>>,
+) -> Result<Vec<NamedApiResource>, PokedexError> {
+    let mut keyed = all
+        .into_iter()
+        .map(|p| Ok((p.id(base, "pokemon")?, p)))
+        .collect::<Result<Vec<_>, PokedexError>>()?;
+    let figure = |id: &u32| {
+        let f = details?.get(id)?;
+        match key {
+            SortKey::BaseStatTotal => Some(f.base_stat_total),
+            SortKey::Height => Some(f.height),
+            SortKey::Weight => Some(f.weight),
+            SortKey::Id | SortKey::Name => None,
+        }
+    };
+    keyed.sort_by(|(a_id, a), (b_id, b)| {
+        let ordering = match key {
+            SortKey::Id => a_id.cmp(b_id),
+            SortKey::Name => a.name.cmp(&b.name),
+            _ => match (figure(a_id), figure(b_id)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        let ordering = match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        ordering.then(a_id.cmp(b_id))
+    });
+    Ok(keyed.into_iter().map(|(_, p)| p).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "https://pokeapi.co/api/v2/";
+
+    fn pokemon(id: u32, name: &str) -> NamedApiResource {
+        NamedApiResource {
+            url: format!("{BASE}pokemon/{id}/"),
+            name: name.to_owned(),
+        }
+    }
+
+    /// Heights by id; every other figure is zero.
+    fn heights(heights: &[(u32, u32)]) -> HashMap<u32, Figures> {
+        heights
+            .iter()
+            .map(|&(id, height)| {
+                let figures = Figures {
+                    base_stat_total: 0,
+                    height,
+                    weight: 0,
+                };
+                (id, figures)
+            })
+            .collect()
+    }
+
+    fn sorted_ids(
+        key: SortKey,
+        order: SortOrder,
+        details: Option<&HashMap<u32, Figures>>,
+    ) -> Vec<u32> {
+        let base = Url::parse(BASE).unwrap();
+        let all = vec![
+            pokemon(4, "charmander"),
+            pokemon(1, "bulbasaur"),
+            pokemon(7, "squirtle"),
+            pokemon(25, "pikachu"),
+            pokemon(10, "caterpie"),
+        ];
+        sort_pokemon(all, &base, key, order, details)
+            .unwrap()
+            .iter()
+            .map(|p| p.id(&base, "pokemon").unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_id_and_name() {
+        assert_eq!(
+            sorted_ids(SortKey::Id, SortOrder::Asc, None),
+            [1, 4, 7, 10, 25]
+        );
+        assert_eq!(
+            sorted_ids(SortKey::Name, SortOrder::Desc, None),
+            [7, 25, 4, 10, 1]
+        );
+    }
+
+    #[test]
+    fn sorts_by_figure_in_either_order() {
+        let details = heights(&[(1, 7), (4, 6), (7, 5), (10, 3), (25, 4)]);
+        assert_eq!(
+            sorted_ids(SortKey::Height, SortOrder::Asc, Some(&details)),
+            [10, 25, 7, 4, 1]
+        );
+        assert_eq!(
+            sorted_ids(SortKey::Height, SortOrder::Desc, Some(&details)),
+            [1, 4, 7, 25, 10]
+        );
+    }
+
+    #[test]
+    fn missing_figures_sort_last_in_either_order() {
+        let details = heights(&[(4, 6), (7, 5), (25, 4)]);
+        assert_eq!(
+            sorted_ids(SortKey::Height, SortOrder::Asc, Some(&details)),
+            [25, 7, 4, 1, 10]
+        );
+        assert_eq!(
+            sorted_ids(SortKey::Height, SortOrder::Desc, Some(&details)),
+            [4, 7, 25, 1, 10]
+        );
+        assert_eq!(
+            sorted_ids(SortKey::Weight, SortOrder::Desc, None),
+            [1, 4, 7, 10, 25]
+        );
+    }
+
+    #[test]
+    fn ties_are_broken_by_ascending_id() {
+        let details = heights(&[(1, 7), (4, 3), (7, 7), (10, 3), (25, 7)]);
+        assert_eq!(
+            sorted_ids(SortKey::Height, SortOrder::Asc, Some(&details)),
+            [4, 10, 1, 7, 25]
+        );
+        assert_eq!(
+            sorted_ids(SortKey::Height, SortOrder::Desc, Some(&details)),
+            [1, 7, 25, 4, 10]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use poem_openapi::payload::Json;
+use poem_openapi_derive::{ApiResponse, Object};
+use serde::Serialize;
+
+use super::problem::{failure_response, ProblemDetails, ProblemJson};
+use crate::pokemon_api;
+
+failure_response! {
+    not_found: NotFound;
+    #[derive(ApiResponse)]
+    #[oai(bad_request_handler = "species_bad_request")]
+    pub(super) enum SpeciesResponse {
+        #[oai(status = 200)]
+        Ok(Json<Species>),
+        /// The species id is not a number
+        #[oai(status = 400)]
+        BadRequest(ProblemJson),
+        /// No species with this id
+        #[oai(status = 404)]
+        NotFound(ProblemJson),
+    }
+}
+
+fn species_bad_request(err: poem::Error) -> SpeciesResponse {
+    SpeciesResponse::BadRequest(ProblemDetails::from_bad_request(err))
+}
+
+#[derive(Serialize, Object)]
+pub(super) struct Species {
+    pub id: u32,
+    pub name: String,
+    /// Genus in the requested language, e.g. "Mouse Pokémon"
+    pub genus: Option<String>,
+    /// Pokédex descriptions in the requested language, one per game version
+    pub flavor_text_entries: Vec<FlavorTextEntry>,
+    /// Base capture rate, up to 255; higher is easier to catch
+    pub capture_rate: u32,
+    pub habitat: Option<String>,
+    pub color: String,
+    pub is_legendary: bool,
+    pub is_mythical: bool,
+    pub varieties: Vec<SpeciesVariety>,
+}
+
+#[derive(Serialize, Object)]
+pub(super) struct FlavorTextEntry {
+    pub text: String,
+    pub language: String,
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Object)]
+pub(super) struct SpeciesVariety {
+    /// Name of the Pokémon representing this variety
+    pub name: String,
+    pub is_default: bool,
+}
+
+impl Species {
+    pub fn new(mut s: pokemon_api::PokemonSpecies, language: &str) -> Self {
+        Self {
+            id: s.id,
+            name: s.name,
+            genus: s
+                .genera
+                .drain(..)
+                .find(|g| g.language.name == language)
+                .map(|g| g.genus),
+            flavor_text_entries: s
+                .flavor_text_entries
+                .drain(..)
+                .filter(|f| f.language.name == language)
+                .map(|f| FlavorTextEntry {
+                    // Upstream keeps the line breaks and form feeds of the games' text boxes
+                    text: f
+                        .flavor_text
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    language: f.language.name,
+                    version: f.version.map(|v| v.name),
+                })
+                .collect(),
+            capture_rate: s.capture_rate,
+            habitat: s.habitat.map(|h| h.name),
+            color: s.color.name,
+            is_legendary: s.is_legendary,
+            is_mythical: s.is_mythical,
+            varieties: s
+                .varieties
+                .drain(..)
+                .map(|v| SpeciesVariety {
+                    name: v.pokemon.name,
+                    is_default: v.is_default,
+                })
+                .collect(),
+        }
+    }
+}
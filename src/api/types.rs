@@ -0,0 +1,247 @@
+use poem_openapi::payload::Json;
+use poem_openapi_derive::{ApiResponse, Object};
+use reqwest::Url;
+use serde::Serialize;
+
+use super::{
+    problem::{failure_response, ProblemJson},
+    Pokemon,
+};
+
+use crate::pokemon_api::{self, NamedApiResource, PokedexError};
+
+failure_response! {
+    #[derive(ApiResponse)]
+    pub(super) enum TypeListResponse {
+        #[oai(status = 200)]
+        Ok(Json<Vec<TypeSummary>>),
+    }
+}
+
+failure_response! {
+    not_found: NotFound;
+    #[derive(ApiResponse)]
+    pub(super) enum TypeDetailResponse {
+        #[oai(status = 200)]
+        Ok(Json<TypeDetail>),
+        /// No type with this id or name
+        #[oai(status = 404)]
+        NotFound(ProblemJson),
+    }
+}
+
+failure_response! {
+    #[derive(ApiResponse)]
+    pub(super) enum TypeMatrixResponse {
+        #[oai(status = 200)]
+        Ok(Json<TypeMatrix>),
+    }
+}
+
+#[derive(Serialize, Object)]
+pub(super) struct TypeSummary {
+    pub id: u32,
+    pub name: String,
+}
+
+impl TypeSummary {
+    pub fn new(r: NamedApiResource, base: &Url) -> Result<Self, PokedexError> {
+        Ok(Self {
+            id: r.id(base, "type")?,
+            name: r.name,
+        })
+    }
+}
+
+#[derive(Serialize, Object)]
+pub(super) struct TypeDetail {
+    pub id: u32,
+    pub name: String,
+    /// Generation the type was introduced in, e.g. "generation-i"
+    pub generation: String,
+    /// "physical" or "special" for types that decided the damage class before generation IV
+    pub damage_class: Option<String>,
+    pub damage_relations: DamageRelations,
+    /// Pokémon having this type in any slot
+    pub pokemon: Vec<Pokemon>,
+}
+
+/// Names of the types this one deals or takes modified damage to or from.
+#[derive(Serialize, Object)]
+pub(super) struct DamageRelations {
+    pub double_damage_to: Vec<String>,
+    pub half_damage_to: Vec<String>,
+    pub no_damage_to: Vec<String>,
+    pub double_damage_from: Vec<String>,
+    pub half_damage_from: Vec<String>,
+    pub no_damage_from: Vec<String>,
+}
+
+fn names(resources: Vec<NamedApiResource>) -> Vec<String> {
+    resources.into_iter().map(|r| r.name).collect()
+}
+
+impl TypeDetail {
+    pub fn new(t: pokemon_api::PokemonType, base: &Url) -> Result<Self, PokedexError> {
+        let r = t.damage_relations;
+        Ok(Self {
+            id: t.id,
+            name: t.name,
+            generation: t.generation.name,
+            damage_class: t.move_damage_class.map(|c| c.name),
+            damage_relations: DamageRelations {
+                double_damage_to: names(r.double_damage_to),
+                half_damage_to: names(r.half_damage_to),
+                no_damage_to: names(r.no_damage_to),
+                double_damage_from: names(r.double_damage_from),
+                half_damage_from: names(r.half_damage_from),
+                no_damage_from: names(r.no_damage_from),
+            },
+            pokemon: t
+                .pokemon
+                .into_iter()
+                .map(|p| Pokemon::new(p.pokemon, base))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Damage multipliers between every pair of types.
+#[derive(Serialize, Object)]
+pub(super) struct TypeMatrix {
+    /// Type names, in the order of the rows and columns of `multipliers`
+    pub types: Vec<String>,
+    /// `multipliers[a][d]` is the damage multiplier of a move of type `types[a]`
+    /// against a Pokémon of type `types[d]`: 0, 0.5, 1 or 2
+    pub multipliers: Vec<Vec<f64>>,
+}
+
+impl TypeMatrix {
+    pub fn new(mut types: Vec<pokemon_api::PokemonType>) -> Self {
+        // Skip the placeholder types, like "unknown" or "shadow", no Pokémon has
+        types.retain(|t| !t.pokemon.is_empty());
+        types.sort_by_key(|t| t.id);
+        let multipliers = types
+            .iter()
+            .map(|attacking| {
+                let r = &attacking.damage_relations;
+                let multiplier = |name: &str| {
+                    let hits = |list: &[NamedApiResource]| list.iter().any(|t| t.name == name);
+                    if hits(&r.no_damage_to) {
+                        0.0
+                    } else if hits(&r.half_damage_to) {
+                        0.5
+                    } else if hits(&r.double_damage_to) {
+                        2.0
+                    } else {
+                        1.0
+                    }
+                };
+                types
+                    .iter()
+                    .map(|defending| multiplier(&defending.name))
+                    .collect()
+            })
+            .collect();
+        Self {
+            types: types.into_iter().map(|t| t.name).collect(),
+            multipliers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const BASE: &str = "https://pokeapi.co/api/v2/";
+
+    fn refs(names: &[&str]) -> serde_json::Value {
+        names
+            .iter()
+            .map(|name| json!({"name": name, "url": format!("{BASE}type/{name}/")}))
+            .collect()
+    }
+
+    /// Type dealing double, half and no damage to the given types; placeholder types have
+    /// no Pokémon.
+    fn pokemon_type(
+        id: u32,
+        name: &str,
+        [double, half, none]: [&[&str]; 3],
+        placeholder: bool,
+    ) -> pokemon_api::PokemonType {
+        let pokemon = if placeholder {
+            json!([])
+        } else {
+            let mew = json!({"name": "mew", "url": format!("{BASE}pokemon/151/")});
+            json!([{"slot": 1, "pokemon": mew}])
+        };
+        serde_json::from_value(json!({
+            "id": id,
+            "name": name,
+            "damage_relations": {
+                "double_damage_to": refs(double),
+                "half_damage_to": refs(half),
+                "no_damage_to": refs(none),
+                "double_damage_from": [],
+                "half_damage_from": [],
+                "no_damage_from": [],
+            },
+            "generation": {"name": "generation-i", "url": format!("{BASE}generation/1/")},
+            "move_damage_class": null,
+            "pokemon": pokemon,
+        }))
+        .unwrap()
+    }
+
+    fn matrix() -> TypeMatrix {
+        TypeMatrix::new(vec![
+            pokemon_type(12, "grass", [&["water"], &["fire", "grass"], &[]], false),
+            pokemon_type(10001, "unknown", [&[], &[], &[]], true),
+            pokemon_type(1, "normal", [&[], &["rock"], &["ghost"]], false),
+            pokemon_type(10, "fire", [&["grass"], &["fire", "water"], &[]], false),
+            pokemon_type(8, "ghost", [&["ghost"], &[], &["normal"]], false),
+            pokemon_type(11, "water", [&["fire"], &["water", "grass"], &[]], false),
+            pokemon_type(10002, "shadow", [&[], &[], &[]], true),
+        ])
+    }
+
+    #[test]
+    fn orders_types_by_id_without_placeholders() {
+        assert_eq!(
+            matrix().types,
+            ["normal", "ghost", "fire", "water", "grass"]
+        );
+    }
+
+    #[test]
+    fn multiplies_by_damage_relations() {
+        let matrix = matrix();
+        let multiplier = |attacking: &str, defending: &str| {
+            let index = |name| matrix.types.iter().position(|t| t == name).unwrap();
+            matrix.multipliers[index(attacking)][index(defending)]
+        };
+        let cases = [
+            ("fire", "grass", 2.0),
+            ("grass", "fire", 0.5),
+            ("fire", "water", 0.5),
+            ("water", "fire", 2.0),
+            ("normal", "ghost", 0.0),
+            ("ghost", "normal", 0.0),
+            ("ghost", "ghost", 2.0),
+            ("water", "normal", 1.0),
+            ("normal", "normal", 1.0),
+        ];
+        for (attacking, defending, expected) in cases {
+            assert_eq!(
+                multiplier(attacking, defending),
+                expected,
+                "{attacking} -> {defending}"
+            );
+        }
+        assert!(matrix.multipliers.iter().all(|row| row.len() == 5));
+    }
+}
@@ -0,0 +1,538 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Parser;
+use reqwest::Url;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing_subscriber::EnvFilter;
+
+use crate::pokemon_api::{CircuitBreakerConfig, DiskCacheConfig, MemoryCacheConfig, RetryPolicy};
+
+/// REST API serving Pokémon data from PokeAPI.
+///
+/// Every flag can also be set through its environment variable; both take
+/// precedence over the configuration file.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Cli {
+    /// Path to a TOML configuration file
+    #[arg(long, env = "POKEDEX_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address to listen on
+    #[arg(long, env = "POKEDEX_BIND")]
+    bind: Option<String>,
+    /// Url the API is reachable at, advertised in the OpenAPI spec
+    #[arg(long, env = "POKEDEX_PUBLIC_URL")]
+    public_url: Option<String>,
+    /// Title of the API in the OpenAPI spec
+    #[arg(long, env = "POKEDEX_API_TITLE")]
+    api_title: Option<String>,
+    /// Version of the API in the OpenAPI spec
+    #[arg(long, env = "POKEDEX_API_VERSION")]
+    api_version: Option<String>,
+    /// Key signing pagination cursors
+    #[arg(long, env = "POKEDEX_CURSOR_SECRET", hide_env_values = true)]
+    cursor_secret: Option<String>,
+    /// tracing filter directives
+    #[arg(long, env = "POKEDEX_LOG")]
+    log_filter: Option<String>,
+    /// Base url of the PokeAPI instance to query
+    #[arg(long, env = "POKEDEX_UPSTREAM_URL")]
+    upstream_url: Option<String>,
+    /// Serve data from a local PokeAPI dump instead of querying upstream
+    #[arg(long, env = "POKEDEX_OFFLINE_DIR")]
+    offline_dir: Option<PathBuf>,
+    /// Timeout of a single upstream attempt, in seconds
+    #[arg(long, env = "POKEDEX_UPSTREAM_TIMEOUT_SECS")]
+    upstream_timeout_secs: Option<u64>,
+    /// Total number of attempts per upstream request, 1 disables retries
+    #[arg(long, env = "POKEDEX_RETRY_MAX_ATTEMPTS")]
+    retry_max_attempts: Option<u32>,
+    #[arg(long, env = "POKEDEX_RETRY_INITIAL_BACKOFF_MS")]
+    retry_initial_backoff_ms: Option<u64>,
+    #[arg(long, env = "POKEDEX_RETRY_MAX_BACKOFF_MS")]
+    retry_max_backoff_ms: Option<u64>,
+    #[arg(long, env = "POKEDEX_RETRY_MULTIPLIER")]
+    retry_multiplier: Option<f64>,
+    /// Fraction of each backoff randomly taken off, between 0 and 1
+    #[arg(long, env = "POKEDEX_RETRY_JITTER")]
+    retry_jitter: Option<f64>,
+    /// Comma-separated upstream statuses that are retried
+    #[arg(long, env = "POKEDEX_RETRY_STATUSES", value_delimiter = ',')]
+    retry_statuses: Option<Vec<u16>>,
+    #[arg(long, env = "POKEDEX_CIRCUIT_BREAKER_ENABLED")]
+    circuit_breaker_enabled: Option<bool>,
+    #[arg(long, env = "POKEDEX_CIRCUIT_BREAKER_FAILURE_THRESHOLD")]
+    circuit_breaker_failure_threshold: Option<u32>,
+    #[arg(long, env = "POKEDEX_CIRCUIT_BREAKER_OPEN_SECS")]
+    circuit_breaker_open_secs: Option<u64>,
+    #[arg(long, env = "POKEDEX_CIRCUIT_BREAKER_HALF_OPEN_MAX_CALLS")]
+    circuit_breaker_half_open_max_calls: Option<u32>,
+    /// Number of responses kept in memory, 0 disables the memory cache
+    #[arg(long, env = "POKEDEX_CACHE_MEMORY_CAPACITY")]
+    cache_memory_capacity: Option<usize>,
+    #[arg(long, env = "POKEDEX_CACHE_MEMORY_TTL_SECS")]
+    cache_memory_ttl_secs: Option<u64>,
+    /// Directory for the persistent response cache
+    #[arg(long, env = "POKEDEX_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    #[arg(long, env = "POKEDEX_CACHE_DISK_MAX_AGE_SECS")]
+    cache_disk_max_age_secs: Option<u64>,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum ConfigError {
+    #[error("Could not read config file {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Could not parse config file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Settings {
+    pub server: ServerSettings,
+    pub log: LogSettings,
+    pub upstream: UpstreamSettings,
+    pub cache: CacheSettings,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ServerSettings {
+    pub bind: String,
+    pub public_url: String,
+    pub title: String,
+    pub version: String,
+    /// Key signing pagination cursors; a random one is generated at startup if unset
+    pub cursor_secret: Option<String>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind: ":::3001".to_owned(),
+            public_url: "http://localhost:3001/api".to_owned(),
+            title: "Demo".to_owned(),
+            version: "1.0".to_owned(),
+            cursor_secret: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct LogSettings {
+    pub filter: String,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            filter: "poem=trace".to_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct UpstreamSettings {
+    pub base_url: String,
+    pub offline_dir: Option<PathBuf>,
+    /// Timeout of a single upstream attempt
+    pub timeout_secs: u64,
+    pub retry: RetrySettings,
+    pub circuit_breaker: CircuitBreakerSettings,
+}
+
+impl Default for UpstreamSettings {
+    fn default() -> Self {
+        Self {
+            base_url: "https://pokeapi.co/api/v2/".to_owned(),
+            offline_dir: None,
+            timeout_secs: 10,
+            retry: RetrySettings::default(),
+            circuit_breaker: CircuitBreakerSettings::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct RetrySettings {
+    /// Total number of attempts per upstream request, 1 disables retries
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub multiplier: f64,
+    /// Fraction of each backoff randomly taken off, between 0 and 1
+    pub jitter: f64,
+    pub retryable_statuses: Vec<u16>,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        Self {
+            max_attempts: policy.max_attempts,
+            initial_backoff_ms: policy.initial_backoff.as_millis() as u64,
+            max_backoff_ms: policy.max_backoff.as_millis() as u64,
+            multiplier: policy.multiplier,
+            jitter: policy.jitter,
+            retryable_statuses: policy.retryable_statuses,
+        }
+    }
+}
+
+impl RetrySettings {
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            initial_backoff: Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: Duration::from_millis(self.max_backoff_ms),
+            multiplier: self.multiplier,
+            jitter: self.jitter,
+            retryable_statuses: self.retryable_statuses.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct CircuitBreakerSettings {
+    pub enabled: bool,
+    pub failure_threshold: u32,
+    pub open_secs: u64,
+    pub half_open_max_calls: u32,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        let config = CircuitBreakerConfig::default();
+        Self {
+            enabled: true,
+            failure_threshold: config.failure_threshold,
+            open_secs: config.open_duration.as_secs(),
+            half_open_max_calls: config.half_open_max_calls,
+        }
+    }
+}
+
+impl CircuitBreakerSettings {
+    pub fn config(&self) -> Option<CircuitBreakerConfig> {
+        self.enabled.then(|| CircuitBreakerConfig {
+            failure_threshold: self.failure_threshold,
+            open_duration: Duration::from_secs(self.open_secs),
+            half_open_max_calls: self.half_open_max_calls,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct CacheSettings {
+    /// Number of responses kept in memory, 0 disables the memory cache
+    pub memory_capacity: usize,
+    pub memory_ttl_secs: u64,
+    pub disk_dir: Option<PathBuf>,
+    pub disk_max_age_secs: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        let memory = MemoryCacheConfig::default();
+        Self {
+            memory_capacity: memory.capacity,
+            memory_ttl_secs: memory.ttl.as_secs(),
+            disk_dir: None,
+            disk_max_age_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl CacheSettings {
+    pub fn memory(&self) -> Option<MemoryCacheConfig> {
+        (self.memory_capacity > 0).then(|| MemoryCacheConfig {
+            capacity: self.memory_capacity,
+            ttl: Duration::from_secs(self.memory_ttl_secs),
+        })
+    }
+
+    pub fn disk(&self) -> Option<DiskCacheConfig> {
+        self.disk_dir.as_ref().map(|dir| DiskCacheConfig {
+            dir: dir.clone(),
+            max_age: Duration::from_secs(self.disk_max_age_secs),
+        })
+    }
+}
+
+impl Settings {
+    /// Defaults, overlaid with the config file, overlaid with environment variables and flags.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::from_cli(Cli::parse())
+    }
+
+    fn from_cli(cli: Cli) -> Result<Self, ConfigError> {
+        let mut settings = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        settings.apply(cli);
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+        toml::from_str(&content).map_err(|source| ConfigError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    fn apply(&mut self, cli: Cli) {
+        fn set<T>(setting: &mut T, value: Option<T>) {
+            if let Some(value) = value {
+                *setting = value;
+            }
+        }
+        let (server, upstream, cache) = (&mut self.server, &mut self.upstream, &mut self.cache);
+        set(&mut server.bind, cli.bind);
+        set(&mut server.public_url, cli.public_url);
+        set(&mut server.title, cli.api_title);
+        set(&mut server.version, cli.api_version);
+        set(&mut server.cursor_secret, cli.cursor_secret.map(Some));
+        set(&mut self.log.filter, cli.log_filter);
+        set(&mut upstream.base_url, cli.upstream_url);
+        set(&mut upstream.offline_dir, cli.offline_dir.map(Some));
+        set(&mut upstream.timeout_secs, cli.upstream_timeout_secs);
+        let retry = &mut upstream.retry;
+        set(&mut retry.max_attempts, cli.retry_max_attempts);
+        set(&mut retry.initial_backoff_ms, cli.retry_initial_backoff_ms);
+        set(&mut retry.max_backoff_ms, cli.retry_max_backoff_ms);
+        set(&mut retry.multiplier, cli.retry_multiplier);
+        set(&mut retry.jitter, cli.retry_jitter);
+        set(&mut retry.retryable_statuses, cli.retry_statuses);
+        let breaker = &mut upstream.circuit_breaker;
+        set(&mut breaker.enabled, cli.circuit_breaker_enabled);
+        set(
+            &mut breaker.failure_threshold,
+            cli.circuit_breaker_failure_threshold,
+        );
+        set(&mut breaker.open_secs, cli.circuit_breaker_open_secs);
+        set(
+            &mut breaker.half_open_max_calls,
+            cli.circuit_breaker_half_open_max_calls,
+        );
+        set(&mut cache.memory_capacity, cli.cache_memory_capacity);
+        set(&mut cache.memory_ttl_secs, cli.cache_memory_ttl_secs);
+        set(&mut cache.disk_dir, cli.cache_dir.map(Some));
+        set(&mut cache.disk_max_age_secs, cli.cache_disk_max_age_secs);
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: String| Err(ConfigError::Invalid(msg));
+        match self.server.bind.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => {}
+            _ => return invalid(format!("server.bind {:?} has no port", self.server.bind)),
+        }
+        match Url::parse(&self.server.public_url) {
+            Ok(url) if !url.cannot_be_a_base() => {}
+            _ => {
+                return invalid(format!(
+                    "server.public_url {:?} is not a valid url",
+                    self.server.public_url
+                ))
+            }
+        }
+        if matches!(&self.server.cursor_secret, Some(secret) if secret.is_empty()) {
+            return invalid("server.cursor_secret must not be empty".to_owned());
+        }
+        if let Err(e) = EnvFilter::try_new(&self.log.filter) {
+            return invalid(format!("log.filter {:?}: {e}", self.log.filter));
+        }
+        match Url::parse(&self.upstream.base_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.path().ends_with('/') => {}
+            _ => {
+                return invalid(format!(
+                    "upstream.base_url {:?} must be an http(s) url ending with '/'",
+                    self.upstream.base_url
+                ))
+            }
+        }
+        if let Some(dir) = &self.upstream.offline_dir {
+            if !dir.is_dir() {
+                return invalid(format!(
+                    "upstream.offline_dir {} is not a directory",
+                    dir.display()
+                ));
+            }
+        }
+        if self.upstream.timeout_secs == 0 {
+            return invalid("upstream.timeout_secs must be positive".to_owned());
+        }
+        let retry = &self.upstream.retry;
+        if retry.max_attempts == 0 {
+            return invalid("upstream.retry.max_attempts must be at least 1".to_owned());
+        }
+        if !(retry.multiplier >= 1.0 && retry.multiplier.is_finite()) {
+            return invalid("upstream.retry.multiplier must be at least 1".to_owned());
+        }
+        if !(0.0..=1.0).contains(&retry.jitter) {
+            return invalid("upstream.retry.jitter must be between 0 and 1".to_owned());
+        }
+        if retry.initial_backoff_ms > retry.max_backoff_ms {
+            return invalid(
+                "upstream.retry.initial_backoff_ms must not exceed max_backoff_ms".to_owned(),
+            );
+        }
+        let breaker = &self.upstream.circuit_breaker;
+        if breaker.failure_threshold == 0 || breaker.half_open_max_calls == 0 {
+            return invalid(
+                "upstream.circuit_breaker.failure_threshold and half_open_max_calls must be positive"
+                    .to_owned(),
+            );
+        }
+        if self.cache.memory_ttl_secs == 0 {
+            return invalid("cache.memory_ttl_secs must be positive".to_owned());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::try_parse_from(["pokedex"].iter().chain(args)).unwrap()
+    }
+
+    fn settings(args: &[&str]) -> Settings {
+        let mut settings = Settings::default();
+        settings.apply(cli(args));
+        settings
+    }
+
+    /// Configuration file removed again when dropped.
+    struct ConfigFile(PathBuf);
+
+    impl ConfigFile {
+        fn new(name: &str, content: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("pokedex-config-{}-{name}.toml", std::process::id()));
+            fs::write(&path, content).unwrap();
+            Self(path)
+        }
+
+        /// Loads the file with `args` on the command line after it.
+        fn load(&self, args: &[&str]) -> Result<Settings, ConfigError> {
+            let path = self.0.to_str().unwrap();
+            let args: Vec<_> = ["--config", path].iter().chain(args).copied().collect();
+            Settings::from_cli(cli(&args))
+        }
+    }
+
+    impl Drop for ConfigFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn flags_override_defaults() {
+        let settings = settings(&[
+            "--upstream-timeout-secs",
+            "3",
+            "--retry-max-attempts",
+            "5",
+            "--retry-statuses",
+            "429,503",
+            "--circuit-breaker-enabled",
+            "false",
+            "--cache-memory-capacity",
+            "0",
+            "--cache-disk-max-age-secs",
+            "60",
+        ]);
+        assert_eq!(settings.upstream.timeout_secs, 3);
+        assert_eq!(settings.upstream.retry.max_attempts, 5);
+        assert_eq!(settings.upstream.retry.retryable_statuses, [429, 503]);
+        assert!(settings.upstream.circuit_breaker.config().is_none());
+        assert!(settings.cache.memory().is_none());
+        assert_eq!(settings.cache.disk_max_age_secs, 60);
+        settings.validate().unwrap();
+    }
+
+    #[test]
+    fn flags_override_file_values_and_keep_the_others() {
+        let file = ConfigFile::new(
+            "layered",
+            r#"
+            [server]
+            title = "Pokédex"
+
+            [upstream]
+            timeout_secs = 3
+
+            [upstream.retry]
+            max_attempts = 7
+            jitter = 0.3
+
+            [cache]
+            memory_capacity = 10
+            "#,
+        );
+        let settings = file
+            .load(&["--retry-jitter", "0.1", "--cache-memory-ttl-secs", "30"])
+            .unwrap();
+        assert_eq!(settings.upstream.retry.jitter, 0.1);
+        assert_eq!(settings.cache.memory_ttl_secs, 30);
+        assert_eq!(settings.server.title, "Pokédex");
+        assert_eq!(settings.upstream.timeout_secs, 3);
+        assert_eq!(settings.upstream.retry.max_attempts, 7);
+        assert_eq!(settings.cache.memory_capacity, 10);
+        // Neither in the file nor on the command line
+        assert_eq!(settings.server.bind, ServerSettings::default().bind);
+    }
+
+    #[test]
+    fn unknown_file_keys_are_rejected() {
+        for (name, content) in [
+            ("top-level", "verbose = true\n"),
+            ("section", "[server]\nport = 3001\n"),
+            ("nested", "[upstream.retry]\nmax_retries = 3\n"),
+        ] {
+            let file = ConfigFile::new(name, content);
+            assert!(
+                matches!(file.load(&[]), Err(ConfigError::Parse { .. })),
+                "{content}"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let file = ConfigFile::new("missing", "");
+        fs::remove_file(&file.0).unwrap();
+        assert!(matches!(file.load(&[]), Err(ConfigError::Read { .. })));
+    }
+
+    #[test]
+    fn overridden_values_are_validated() {
+        let result = Settings::from_cli(cli(&["--retry-jitter", "1.5"]));
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+}
@@ -1,27 +1,37 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use poem::{listener::TcpListener, middleware::Tracing, web::Data, EndpointExt, Route, Server};
-use poem_openapi::{param::Query, payload::Json, OpenApi, OpenApiService};
-use poem_openapi_derive::{ApiResponse, Object};
+use poem::{listener::TcpListener, middleware::Tracing, EndpointExt, Route, Server};
+use poem_openapi::OpenApiService;
 
-use pokemon_api::Pokedex;
-use reqwest::Url;
-use serde::Serialize;
-use thiserror::Error;
-use tracing::{error, Level};
+use api::Api;
+use config::Settings;
+use pokemon_api::{LocalPokedex, Pokedex, PokedexBackend};
+use tracing::{warn, Level};
 
-const BASE_POKEMONAPI_ADDRESS: &'static str = "https://pokeapi.co/api/v2/";
+mod api;
+mod config;
+mod pokemon_api;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
+    let settings = Settings::load()?;
     tracing_subscriber::fmt()
         .with_max_level(Level::INFO)
-        .with_env_filter("poem=trace")
+        .with_env_filter(settings.log.filter.as_str())
         .init();
-    let pokedex = Arc::new(Pokedex::new(BASE_POKEMONAPI_ADDRESS)?);
-    let api_service = OpenApiService::new(Api, "Demo", "1.0").server("http://localhost:3001/api");
+    let pokedex = build_backend(&settings)?;
+    let cursor_key = match &settings.server.cursor_secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => {
+            warn!("server.cursor_secret is not set, pagination cursors will not survive restarts");
+            rand::random::<[u8; 32]>().to_vec()
+        }
+    };
+    let api = Api::new(settings.server.public_url.parse()?, cursor_key);
+    let api_service = OpenApiService::new(api, &settings.server.title, &settings.server.version)
+        .server(&settings.server.public_url);
     let ui = api_service.swagger_ui();
-    Server::new(TcpListener::bind(":::3001"))
+    Server::new(TcpListener::bind(settings.server.bind.clone()))
         .run(
             Route::new()
                 .nest("/api", api_service.data(pokedex).with(Tracing))
@@ -31,133 +41,22 @@ async fn main() -> eyre::Result<()> {
     Ok(())
 }
 
-struct Api;
-
-#[derive(ApiResponse)]
-enum PokemonListResponse {
-    #[oai(status = 200)]
-    Ok(Json<Vec<Pokemon>>),
-    #[oai(status = 500)]
-    InternalServerError,
-}
-
-#[derive(Serialize, Object)]
-struct Pokemon {
-    pub id: u32,
-    pub name: String,
-}
-
-#[OpenApi]
-impl Api {
-    #[oai(path = "/pokemon", method = "get")]
-    #[tracing::instrument(level=tracing::Level::INFO,skip(self, pokedex,))]
-    async fn pokemon(
-        &self,
-        Data(pokedex): Data<&Arc<pokemon_api::Pokedex>>,
-        Query(limit): Query<Option<u32>>,
-        Query(offset): Query<Option<u32>>,
-    ) -> PokemonListResponse {
-        match pokedex
-            .get_all_pokemon(limit.unwrap_or(20), offset.unwrap_or(0))
-            .await
-        {
-            Ok(r) => {
-                let mut data = r.results;
-                #[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
-                #[error("Invalid url in pokemon response: expecting url with numeric id in last segment")]
-                struct InvalidUrlInResponse;
-
-                let result: Result<Vec<Pokemon>, _> = data
-                    .drain(..)
-                    .map(|i| -> Result<Pokemon, InvalidUrlInResponse> {
-                        let url: Url = i.url.parse().map_err(|_| InvalidUrlInResponse)?;
-                        let name = i.name;
-                        let mut segments = url.path_segments().ok_or(InvalidUrlInResponse)?;
-                        let last_segment = segments.nth(3).ok_or(InvalidUrlInResponse)?;
-                        let id = last_segment.parse().map_err(|_| InvalidUrlInResponse)?;
-                        Ok(Pokemon { id, name })
-                    })
-                    .collect();
-                match result {
-                    Ok(r) => PokemonListResponse::Ok(Json(r)),
-                    Err(e) => {
-                        error!(err = %e);
-                        PokemonListResponse::InternalServerError
-                    }
-                }
-            }
-            Err(_) => PokemonListResponse::InternalServerError,
-        }
+fn build_backend(settings: &Settings) -> eyre::Result<Arc<dyn PokedexBackend>> {
+    let base = &settings.upstream.base_url;
+    if let Some(dir) = &settings.upstream.offline_dir {
+        return Ok(Arc::new(LocalPokedex::new(dir, base)?));
     }
-}
-
-mod pokemon_api {
-    use reqwest::Url;
-    use serde::{Deserialize, Serialize};
-    use thiserror::Error;
-    use tracing::instrument;
-
-    #[derive(Deserialize, Serialize)]
-    pub struct Pokemon {
-        pub url: String,
-        pub name: String,
-    }
-
-    #[derive(Deserialize)]
-    pub struct PokemonList {
-        pub count: u32,
-        pub next: Option<String>,
-        pub previous: Option<String>,
-        pub results: Vec<Pokemon>,
-    }
-
-    #[derive(Error, Debug)]
-    pub(crate) enum PokedexError {
-        #[error("Error during http request: {0}")]
-        HttpRequestError(#[from] reqwest::Error),
-        #[error("Invalid base url")]
-        InvalidBaseUrl,
+    let mut pokedex = Pokedex::new(base)?
+        .with_timeout(Duration::from_secs(settings.upstream.timeout_secs))
+        .with_retry_policy(settings.upstream.retry.policy());
+    if let Some(config) = settings.cache.memory() {
+        pokedex = pokedex.with_memory_cache(config);
     }
-    pub(crate) struct Pokedex {
-        http_client: reqwest::Client,
-        base: Url,
+    if let Some(config) = settings.cache.disk() {
+        pokedex = pokedex.with_disk_cache(config);
     }
-
-    impl Pokedex {
-        #[instrument(skip(self), err)]
-        pub async fn get_all_pokemon(
-            &self,
-            limit: u32,
-            offset: u32,
-        ) -> Result<PokemonList, PokedexError> {
-            let url = self
-                .base
-                .join("pokemon")
-                .expect("could not join with base url");
-            Ok(self
-                .http_client
-                .get(url)
-                .query(&[("limit", limit), ("offset", offset)])
-                .send()
-                .await?
-                .error_for_status()?
-                .json()
-                .await?)
-        }
-
-        pub fn new(base: &str) -> Result<Self, PokedexError> {
-            let client = reqwest::Client::builder().build().unwrap();
-            let base = Url::try_from(base).map_err(|_| PokedexError::InvalidBaseUrl)?;
-            if base.cannot_be_a_base() {
-                return Err(PokedexError::InvalidBaseUrl);
-            }
-            if base.scheme() != "http" && base.scheme() != "https" {
-                return Err(PokedexError::InvalidBaseUrl);
-            }
-            Ok(Self {
-                base,
-                http_client: client,
-            })
-        }
+    if let Some(config) = settings.upstream.circuit_breaker.config() {
+        pokedex = pokedex.with_circuit_breaker(config);
     }
+    Ok(Arc::new(pokedex))
 }
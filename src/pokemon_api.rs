@@ -0,0 +1,970 @@
+use std::{collections::HashSet, io, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{future::try_join_all, stream, Stream, StreamExt, TryStreamExt};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use tracing::{debug, info_span, instrument, warn, Instrument};
+
+mod cache;
+mod circuit_breaker;
+mod disk_cache;
+mod models;
+mod offline;
+mod retry;
+mod single_flight;
+mod validators;
+
+use cache::MemoryCache;
+pub(crate) use cache::{CacheStats, MemoryCacheConfig};
+use circuit_breaker::CircuitBreaker;
+pub(crate) use circuit_breaker::{CircuitBreakerConfig, CircuitBreakerStatus, CircuitState};
+use disk_cache::DiskCache;
+pub(crate) use disk_cache::DiskCacheConfig;
+pub(crate) use models::*;
+pub(crate) use offline::LocalPokedex;
+pub(crate) use retry::RetryPolicy;
+use single_flight::SingleFlight;
+use validators::Validators;
+
+#[derive(Error, Debug)]
+pub(crate) enum PokedexError {
+    #[error("Error during http request: {0}")]
+    HttpRequestError(#[from] reqwest::Error),
+    #[error("Invalid base url")]
+    InvalidBaseUrl,
+    #[error("Resource not found")]
+    NotFound,
+    #[error("Invalid response body: {0}")]
+    InvalidResponseBody(#[from] serde_json::Error),
+    #[error("Error reading local data: {0}")]
+    LocalDataError(#[from] io::Error),
+    #[error("Upstream responded with {status}")]
+    UnexpectedStatus {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    #[error("Upstream unavailable, circuit breaker is open")]
+    CircuitOpen { retry_after: Duration },
+    #[error("Invalid resource url in response: {0}")]
+    InvalidResourceUrl(String),
+    #[error("No {filter} named {value:?}")]
+    UnknownFilterValue { filter: &'static str, value: String },
+    /// Failure of an upstream request shared by concurrent identical requests
+    #[error("{0}")]
+    Coalesced(Arc<PokedexError>),
+}
+
+impl PokedexError {
+    /// Copy of an error for every caller sharing a coalesced request; errors that cannot
+    /// be copied are shared as [`PokedexError::Coalesced`].
+    fn shared(error: &Arc<PokedexError>) -> Self {
+        match error.as_ref() {
+            PokedexError::InvalidBaseUrl => PokedexError::InvalidBaseUrl,
+            PokedexError::NotFound => PokedexError::NotFound,
+            PokedexError::UnexpectedStatus {
+                status,
+                retry_after,
+            } => PokedexError::UnexpectedStatus {
+                status: *status,
+                retry_after: *retry_after,
+            },
+            PokedexError::CircuitOpen { retry_after } => PokedexError::CircuitOpen {
+                retry_after: *retry_after,
+            },
+            PokedexError::InvalidResourceUrl(url) => PokedexError::InvalidResourceUrl(url.clone()),
+            PokedexError::UnknownFilterValue { filter, value } => {
+                PokedexError::UnknownFilterValue {
+                    filter,
+                    value: value.clone(),
+                }
+            }
+            PokedexError::Coalesced(inner) => PokedexError::Coalesced(inner.clone()),
+            PokedexError::HttpRequestError(_)
+            | PokedexError::InvalidResponseBody(_)
+            | PokedexError::LocalDataError(_) => PokedexError::Coalesced(error.clone()),
+        }
+    }
+
+    /// Whether the error indicates upstream itself is unhealthy, as opposed to a bad request.
+    fn is_upstream_failure(&self) -> bool {
+        match self {
+            PokedexError::HttpRequestError(_) => true,
+            PokedexError::UnexpectedStatus { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Criteria for listing Pokémon; a Pokémon must match all the given ones.
+#[derive(Debug, Default)]
+pub(crate) struct PokemonFilter {
+    pub type_: Option<String>,
+    /// Generation id or name, e.g. "3" or "generation-iii"
+    pub generation: Option<String>,
+    pub ability: Option<String>,
+    pub habitat: Option<String>,
+}
+
+impl PokemonFilter {
+    pub fn is_empty(&self) -> bool {
+        self.type_.is_none()
+            && self.generation.is_none()
+            && self.ability.is_none()
+            && self.habitat.is_none()
+    }
+}
+
+/// Lookups in flight for a single [`PokedexBackend::get_pokemon_batch`] call.
+const BATCH_CONCURRENCY: usize = 8;
+
+/// Page size [`Pokedex::list`] requests collections in.
+const LIST_PAGE_SIZE: u32 = 100;
+
+/// Page size large enough to fetch the whole Pokémon index at once.
+const FULL_INDEX_LIMIT: u32 = 100_000;
+
+/// Narrows `ids` down to the ids of `members`, or starts it from them.
+fn restrict<'a>(
+    ids: &mut Option<HashSet<u32>>,
+    members: impl Iterator<Item = &'a NamedApiResource>,
+    base: &Url,
+    kind: &str,
+) -> Result<(), PokedexError> {
+    let members = members
+        .map(|r| r.id(base, kind))
+        .collect::<Result<HashSet<_>, _>>()?;
+    *ids = Some(match ids.take() {
+        Some(ids) => ids.intersection(&members).copied().collect(),
+        None => members,
+    });
+    Ok(())
+}
+
+/// Reports a missing membership list as an unknown filter value rather than a missing resource.
+fn unknown_filter(filter: &'static str, value: &str) -> impl FnOnce(PokedexError) -> PokedexError {
+    let value = value.to_owned();
+    move |e| match e {
+        PokedexError::NotFound => PokedexError::UnknownFilterValue { filter, value },
+        e => e,
+    }
+}
+
+/// Operational state of a backend, reported by health checks and metrics.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BackendStatus {
+    pub circuit_breaker: Option<CircuitBreakerStatus>,
+    pub memory_cache: Option<CacheStats>,
+}
+
+/// Source of Pokémon data the API handlers are served from.
+///
+/// Implemented by the reqwest-based [`Pokedex`]; other backends (fakes, caches,
+/// offline datasets) can be plugged in at startup.
+#[async_trait]
+pub(crate) trait PokedexBackend: Send + Sync {
+    /// Url resource links are resolved and parsed against.
+    fn base_url(&self) -> &Url;
+    async fn list_pokemon(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<NamedApiResourceList, PokedexError>;
+    async fn get_pokemon_by_id(&self, id: u32) -> Result<PokemonDetail, PokedexError>;
+    async fn get_pokemon_by_name(&self, name: &str) -> Result<PokemonDetail, PokedexError>;
+    async fn get_species(&self, id: u32) -> Result<PokemonSpecies, PokedexError>;
+    async fn get_evolution_chain(&self, id: u32) -> Result<EvolutionChain, PokedexError>;
+    async fn list_types(&self) -> Result<NamedApiResourceList, PokedexError>;
+    async fn get_type(&self, id_or_name: &str) -> Result<PokemonType, PokedexError>;
+    async fn get_ability(&self, id_or_name: &str) -> Result<Ability, PokedexError>;
+    async fn get_generation(&self, id_or_name: &str) -> Result<Generation, PokedexError>;
+    async fn get_habitat(&self, id_or_name: &str) -> Result<PokemonHabitat, PokedexError>;
+
+    /// Looks a Pokémon up by numeric id, or by name otherwise.
+    async fn find_pokemon(&self, id_or_name: &str) -> Result<PokemonDetail, PokedexError> {
+        match id_or_name.parse() {
+            Ok(id) => self.get_pokemon_by_id(id).await,
+            Err(_) => self.get_pokemon_by_name(&id_or_name.to_lowercase()).await,
+        }
+    }
+
+    /// Looks up several Pokémon concurrently, returning each outcome in input order.
+    async fn get_pokemon_batch(
+        &self,
+        ids_or_names: &[String],
+    ) -> Vec<Result<PokemonDetail, PokedexError>> {
+        // Collected first: a closure inside the stream trips up the `Send` check of async_trait
+        let lookups: Vec<_> = ids_or_names
+            .iter()
+            .map(|id_or_name| self.find_pokemon(id_or_name))
+            .collect();
+        stream::iter(lookups)
+            .buffered(BATCH_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// Follows pokemon → species → evolution chain.
+    async fn get_evolution_chain_of(
+        &self,
+        pokemon: &PokemonDetail,
+    ) -> Result<EvolutionChain, PokedexError> {
+        let species_id = pokemon.species.id(self.base_url(), "pokemon-species")?;
+        let species = self.get_species(species_id).await?;
+        let chain = species.evolution_chain.ok_or(PokedexError::NotFound)?;
+        self.get_evolution_chain(chain.id(self.base_url(), "evolution-chain")?)
+            .await
+    }
+
+    /// Fetches every type in the catalogue concurrently.
+    async fn get_all_types(&self) -> Result<Vec<PokemonType>, PokedexError> {
+        let list = self.list_types().await?;
+        try_join_all(list.results.iter().map(|t| self.get_type(&t.name))).await
+    }
+
+    /// Names and urls of every Pokémon, in id order.
+    async fn pokemon_index(&self) -> Result<Vec<NamedApiResource>, PokedexError> {
+        Ok(self.list_pokemon(FULL_INDEX_LIMIT, 0).await?.results)
+    }
+
+    /// Every Pokémon matching the filter, in id order.
+    ///
+    /// Generations and habitats list species rather than Pokémon; a species matches
+    /// through its default Pokémon, which shares its id, so alternate forms are only
+    /// matched by type and ability.
+    async fn filter_pokemon(
+        &self,
+        filter: &PokemonFilter,
+    ) -> Result<Vec<NamedApiResource>, PokedexError> {
+        let mut ids = None;
+        if let Some(name) = &filter.type_ {
+            let t = self
+                .get_type(name)
+                .await
+                .map_err(unknown_filter("type", name))?;
+            let members = t.pokemon.iter().map(|p| &p.pokemon);
+            restrict(&mut ids, members, self.base_url(), "pokemon")?;
+        }
+        if let Some(name) = &filter.ability {
+            let ability = self
+                .get_ability(name)
+                .await
+                .map_err(unknown_filter("ability", name))?;
+            let members = ability.pokemon.iter().map(|p| &p.pokemon);
+            restrict(&mut ids, members, self.base_url(), "pokemon")?;
+        }
+        if let Some(name) = &filter.generation {
+            let generation = self
+                .get_generation(name)
+                .await
+                .map_err(unknown_filter("generation", name))?;
+            let members = generation.pokemon_species.iter();
+            restrict(&mut ids, members, self.base_url(), "pokemon-species")?;
+        }
+        if let Some(name) = &filter.habitat {
+            let habitat = self
+                .get_habitat(name)
+                .await
+                .map_err(unknown_filter("habitat", name))?;
+            let members = habitat.pokemon_species.iter();
+            restrict(&mut ids, members, self.base_url(), "pokemon-species")?;
+        }
+        let index = self.pokemon_index().await?;
+        let Some(ids) = ids else {
+            return Ok(index);
+        };
+        let mut matching = Vec::with_capacity(ids.len());
+        for p in index {
+            if ids.contains(&p.id(self.base_url(), "pokemon")?) {
+                matching.push(p);
+            }
+        }
+        Ok(matching)
+    }
+
+    fn status(&self) -> BackendStatus {
+        BackendStatus::default()
+    }
+}
+
+/// Numeric id of a resource of `kind` from its url, e.g. 25 for `<base>pokemon-species/25/`.
+///
+/// Relative urls, as found in offline data dumps, are resolved against `base` first.
+/// Urls under `base` must then be exactly `<base><kind>/<id>/`, whatever path `base` is
+/// mounted at; urls elsewhere, like the upstream links a mirror passes through unchanged,
+/// only need to end with `<kind>/<id>`. The trailing slash is optional in both cases.
+fn resource_id(base: &Url, kind: &str, url: &str) -> Result<u32, PokedexError> {
+    let invalid = || PokedexError::InvalidResourceUrl(url.to_owned());
+    let resolved = base.join(url).map_err(|_| invalid())?;
+    // Compared as a directory, so that `/api/v2` does not claim `/api/v2x/...`
+    let base_path = match base.path() {
+        path if path.ends_with('/') => path.to_owned(),
+        path => format!("{path}/"),
+    };
+    let (path, under_base) = match resolved.path().strip_prefix(base_path.as_str()) {
+        Some(rest) if resolved.origin() == base.origin() => (rest, true),
+        _ => (resolved.path(), false),
+    };
+    let mut segments = path.split('/').filter(|s| !s.is_empty()).rev();
+    match (segments.next(), segments.next()) {
+        (Some(id), Some(k)) if k == kind && !(under_base && segments.next().is_some()) => {
+            id.parse().map_err(|_| invalid())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Outcome of an upstream request.
+enum Upstream {
+    Modified(Bytes, Validators),
+    /// The cached response the request was conditional on is still current; upstream
+    /// may send newer validators for it
+    NotModified(Validators),
+}
+
+pub(crate) struct Pokedex {
+    http_client: reqwest::Client,
+    base: Url,
+    memory_cache: Option<MemoryCache>,
+    disk_cache: Option<DiskCache>,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Option<CircuitBreaker>,
+    timeout: Option<Duration>,
+    in_flight: SingleFlight,
+}
+
+impl Pokedex {
+    /// Every item of a collection such as `"pokemon"`, fetched page by page by following
+    /// the `next` links as the stream is polled.
+    pub fn list<'a, R>(
+        &'a self,
+        resource: &str,
+    ) -> impl Stream<Item = Result<R, PokedexError>> + Send + 'a
+    where
+        R: DeserializeOwned + Send + 'a,
+    {
+        let mut first = self
+            .base
+            .join(resource)
+            .expect("could not join with base url");
+        first
+            .query_pairs_mut()
+            .append_pair("limit", &LIST_PAGE_SIZE.to_string());
+        stream::try_unfold(Some(first), move |url| async move {
+            let Some(url) = url else {
+                return Ok::<_, PokedexError>(None);
+            };
+            let page: ResourceList<R> = self.fetch(url).await?;
+            // Resolved against the base, in case a mirror links pages relatively
+            let next = match page.next {
+                Some(next) => Some(
+                    self.base
+                        .join(&next)
+                        .map_err(|_| PokedexError::InvalidResourceUrl(next))?,
+                ),
+                None => None,
+            };
+            Ok(Some((stream::iter(page.results).map(Ok), next)))
+        })
+        .try_flatten()
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_all_pokemon(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<NamedApiResourceList, PokedexError> {
+        let mut url = self
+            .base
+            .join("pokemon")
+            .expect("could not join with base url");
+        url.query_pairs_mut()
+            .append_pair("limit", &limit.to_string())
+            .append_pair("offset", &offset.to_string());
+        self.fetch(url).await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_pokemon(&self, id_or_name: &str) -> Result<PokemonDetail, PokedexError> {
+        self.fetch(self.resource_url("pokemon", id_or_name)?).await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_species(&self, id: u32) -> Result<PokemonSpecies, PokedexError> {
+        self.fetch(self.resource_url("pokemon-species", &id.to_string())?)
+            .await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_evolution_chain(&self, id: u32) -> Result<EvolutionChain, PokedexError> {
+        self.fetch(self.resource_url("evolution-chain", &id.to_string())?)
+            .await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn list_types(&self) -> Result<NamedApiResourceList, PokedexError> {
+        let mut url = self
+            .base
+            .join("type")
+            .expect("could not join with base url");
+        // The catalogue has a couple dozen entries, fetch it in one page
+        url.query_pairs_mut().append_pair("limit", "100");
+        self.fetch(url).await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_type(&self, id_or_name: &str) -> Result<PokemonType, PokedexError> {
+        self.fetch(self.resource_url("type", id_or_name)?).await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_ability(&self, id_or_name: &str) -> Result<Ability, PokedexError> {
+        self.fetch(self.resource_url("ability", id_or_name)?).await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_generation(&self, id_or_name: &str) -> Result<Generation, PokedexError> {
+        self.fetch(self.resource_url("generation", id_or_name)?)
+            .await
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn get_habitat(&self, id_or_name: &str) -> Result<PokemonHabitat, PokedexError> {
+        self.fetch(self.resource_url("pokemon-habitat", id_or_name)?)
+            .await
+    }
+
+    /// Url of a single resource, e.g. `pokemon/25`; `id` is escaped as a path segment.
+    ///
+    /// Ids that would name the collection or its parent instead, empty, `.` and `..`,
+    /// are reported as not found, like [`LocalPokedex`] does.
+    fn resource_url(&self, kind: &str, id: &str) -> Result<Url, PokedexError> {
+        if matches!(id, "" | "." | "..") {
+            return Err(PokedexError::NotFound);
+        }
+        let mut url = self
+            .base
+            .join(&format!("{kind}/"))
+            .expect("could not join with base url");
+        url.path_segments_mut()
+            .expect("base url can be a base")
+            .pop_if_empty()
+            .push(id);
+        Ok(url)
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, url: Url) -> Result<T, PokedexError> {
+        let body = self.fetch_body(url).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn fetch_body(&self, url: Url) -> Result<Bytes, PokedexError> {
+        let key = url.to_string();
+        if let Some(cache) = &self.memory_cache {
+            if let Some(body) = cache.get(&key) {
+                debug!(url = key, "memory cache hit");
+                return Ok(body);
+            }
+            debug!(url = key, stats = ?cache.stats(), "memory cache miss");
+        }
+        // Concurrent misses of the same url share a single disk read or upstream request
+        self.in_flight.run(&key, self.load(&url, &key)).await
+    }
+
+    async fn load(&self, url: &Url, key: &str) -> Result<Bytes, PokedexError> {
+        let disk_entry = self
+            .disk_cache
+            .as_ref()
+            .and_then(|cache| Some((cache, cache.path_for(&self.base, url)?)));
+        if let Some((cache, path)) = &disk_entry {
+            if let Some((body, validators)) = cache.get(path).await {
+                debug!(url = key, "disk cache hit");
+                if let Some(cache) = &self.memory_cache {
+                    cache.insert(key.to_owned(), body.clone(), validators);
+                }
+                return Ok(body);
+            }
+        }
+        // An expired entry upstream confirms unchanged is refreshed without downloading it again
+        let stale = match self.memory_cache.as_ref().and_then(|c| c.get_stale(key)) {
+            Some(stale) => Some(stale),
+            None => match &disk_entry {
+                Some((cache, path)) => cache.get_stale(path).await,
+                None => None,
+            },
+        };
+        let validators = stale.as_ref().map(|(_, validators)| validators);
+        let (body, validators) = match self.fetch_upstream(url, validators).await? {
+            Upstream::Modified(body, validators) => (body, validators),
+            Upstream::NotModified(fresh) => match stale {
+                Some((body, validators)) => {
+                    debug!(url = key, "cached response revalidated");
+                    (body, validators.merge(fresh))
+                }
+                // Not asked for, so there is nothing to refresh
+                None => {
+                    return Err(PokedexError::UnexpectedStatus {
+                        status: StatusCode::NOT_MODIFIED,
+                        retry_after: None,
+                    })
+                }
+            },
+        };
+        if let Some((cache, path)) = &disk_entry {
+            if let Err(e) = cache.insert(path, &body, &validators).await {
+                warn!(err = %e, path = %path.display(), "could not write disk cache entry");
+            }
+        }
+        if let Some(cache) = &self.memory_cache {
+            cache.insert(key.to_owned(), body.clone(), validators);
+        }
+        Ok(body)
+    }
+
+    async fn fetch_upstream(
+        &self,
+        url: &Url,
+        validators: Option<&Validators>,
+    ) -> Result<Upstream, PokedexError> {
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .send(url, validators)
+                .instrument(info_span!("upstream_request", %url, attempt))
+                .await;
+            match result {
+                Err(e) => match self.retry_policy.delay_for(&e, attempt) {
+                    Some(delay) => {
+                        warn!(err = %e, attempt, ?delay, "retrying upstream request");
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    None => return Err(e),
+                },
+                ok => return ok,
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        url: &Url,
+        validators: Option<&Validators>,
+    ) -> Result<Upstream, PokedexError> {
+        let Some(breaker) = &self.circuit_breaker else {
+            return self.send_unguarded(url, validators).await;
+        };
+        let permit = breaker
+            .acquire()
+            .map_err(|retry_after| PokedexError::CircuitOpen { retry_after })?;
+        let result = self.send_unguarded(url, validators).await;
+        match &result {
+            Err(e) if e.is_upstream_failure() => permit.record_failure(),
+            _ => permit.record_success(),
+        }
+        result
+    }
+
+    /// Requests `url`, conditionally if `validators` of a cached response are given.
+    async fn send_unguarded(
+        &self,
+        url: &Url,
+        validators: Option<&Validators>,
+    ) -> Result<Upstream, PokedexError> {
+        let mut request = self.http_client.get(url.clone());
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        if let Some(validators) = validators {
+            request = validators.apply(request);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            let validators = Validators::from_headers(response.headers());
+            return Ok(Upstream::NotModified(validators));
+        }
+        if status == StatusCode::NOT_FOUND {
+            return Err(PokedexError::NotFound);
+        }
+        if status.is_client_error() || status.is_server_error() {
+            return Err(PokedexError::UnexpectedStatus {
+                status,
+                retry_after: retry::retry_after(response.headers()),
+            });
+        }
+        let validators = Validators::from_headers(response.headers());
+        Ok(Upstream::Modified(response.bytes().await?, validators))
+    }
+
+    pub fn new(base: &str) -> Result<Self, PokedexError> {
+        let client = reqwest::Client::builder().build().unwrap();
+        let base = Url::try_from(base).map_err(|_| PokedexError::InvalidBaseUrl)?;
+        if base.cannot_be_a_base() {
+            return Err(PokedexError::InvalidBaseUrl);
+        }
+        if base.scheme() != "http" && base.scheme() != "https" {
+            return Err(PokedexError::InvalidBaseUrl);
+        }
+        Ok(Self {
+            base,
+            http_client: client,
+            memory_cache: None,
+            disk_cache: None,
+            retry_policy: RetryPolicy::default(),
+            circuit_breaker: None,
+            timeout: None,
+            in_flight: SingleFlight::new(),
+        })
+    }
+
+    /// Serve repeated requests from a bounded in-process cache instead of hitting upstream.
+    pub fn with_memory_cache(mut self, config: MemoryCacheConfig) -> Self {
+        self.memory_cache = Some(MemoryCache::new(config));
+        self
+    }
+
+    /// Persist upstream responses to disk so they survive restarts.
+    pub fn with_disk_cache(mut self, config: DiskCacheConfig) -> Self {
+        self.disk_cache = Some(DiskCache::new(config));
+        self
+    }
+
+    /// Give up on a single upstream attempt after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry transient upstream failures according to `policy`.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Fail fast while upstream keeps failing instead of waiting on every request.
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(CircuitBreaker::new(config));
+        self
+    }
+}
+
+#[async_trait]
+impl PokedexBackend for Pokedex {
+    fn base_url(&self) -> &Url {
+        &self.base
+    }
+
+    async fn pokemon_index(&self) -> Result<Vec<NamedApiResource>, PokedexError> {
+        self.list("pokemon").try_collect().await
+    }
+
+    async fn list_pokemon(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<NamedApiResourceList, PokedexError> {
+        self.get_all_pokemon(limit, offset).await
+    }
+
+    async fn get_pokemon_by_id(&self, id: u32) -> Result<PokemonDetail, PokedexError> {
+        self.get_pokemon(&id.to_string()).await
+    }
+
+    async fn get_pokemon_by_name(&self, name: &str) -> Result<PokemonDetail, PokedexError> {
+        self.get_pokemon(name).await
+    }
+
+    async fn get_species(&self, id: u32) -> Result<PokemonSpecies, PokedexError> {
+        Pokedex::get_species(self, id).await
+    }
+
+    async fn get_evolution_chain(&self, id: u32) -> Result<EvolutionChain, PokedexError> {
+        Pokedex::get_evolution_chain(self, id).await
+    }
+
+    async fn list_types(&self) -> Result<NamedApiResourceList, PokedexError> {
+        Pokedex::list_types(self).await
+    }
+
+    async fn get_type(&self, id_or_name: &str) -> Result<PokemonType, PokedexError> {
+        Pokedex::get_type(self, id_or_name).await
+    }
+
+    async fn get_ability(&self, id_or_name: &str) -> Result<Ability, PokedexError> {
+        Pokedex::get_ability(self, id_or_name).await
+    }
+
+    async fn get_generation(&self, id_or_name: &str) -> Result<Generation, PokedexError> {
+        Pokedex::get_generation(self, id_or_name).await
+    }
+
+    async fn get_habitat(&self, id_or_name: &str) -> Result<PokemonHabitat, PokedexError> {
+        Pokedex::get_habitat(self, id_or_name).await
+    }
+
+    fn status(&self) -> BackendStatus {
+        BackendStatus {
+            circuit_breaker: self.circuit_breaker.as_ref().map(CircuitBreaker::status),
+            memory_cache: self.memory_cache.as_ref().map(MemoryCache::stats),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+
+    fn id(base: &str, kind: &str, url: &str) -> Option<u32> {
+        resource_id(&Url::parse(base).unwrap(), kind, url).ok()
+    }
+
+    #[test]
+    fn resource_id_round_trips() {
+        let bases = [
+            "https://pokeapi.co/",
+            "https://pokeapi.co/api/v2/",
+            "https://pokeapi.co/api/v2",
+            "http://localhost:8080/mirror/deep/path/",
+            "http://localhost:8080/mirror/deep/path",
+        ];
+        for base in bases {
+            let dir = base.trim_end_matches('/');
+            for kind in ["pokemon", "pokemon-species", "evolution-chain", "type"] {
+                for n in [1, 25, 10_277, u32::MAX] {
+                    for url in [
+                        format!("{dir}/{kind}/{n}/"),
+                        format!("{dir}/{kind}/{n}"),
+                        format!("https://elsewhere.example/any/prefix/{kind}/{n}/"),
+                    ] {
+                        assert_eq!(id(base, kind, &url), Some(n), "{base} {url}");
+                        assert_eq!(id(base, "ability", &url), None, "{base} {url}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resource_id_under_base() {
+        for base in [
+            "https://pokeapi.co/",
+            "https://pokeapi.co/api/v2/",
+            "https://mirror.example/mirror/deep/path/",
+        ] {
+            let url = format!("{base}pokemon-species/25/");
+            assert_eq!(id(base, "pokemon-species", &url), Some(25), "{url}");
+            let url = format!("{base}pokemon-species/25");
+            assert_eq!(id(base, "pokemon-species", &url), Some(25), "{url}");
+        }
+    }
+
+    #[test]
+    fn resource_id_base_without_trailing_slash() {
+        let base = "https://mirror.example/mirror/deep/path";
+        let url = "https://mirror.example/mirror/deep/path/pokemon/25/";
+        assert_eq!(id(base, "pokemon", url), Some(25));
+        // A sibling directory sharing the prefix is not under the base, so it is accepted
+        // through the rule for urls elsewhere: ending with the kind and id is enough
+        let url = "https://mirror.example/mirror/deep/pathx/pokemon/25/";
+        assert_eq!(id(base, "pokemon", url), Some(25));
+        let url = "https://mirror.example/mirror/deep/pathx/extra/pokemon/25/";
+        assert_eq!(id(base, "pokemon", url), Some(25));
+    }
+
+    #[test]
+    fn resource_id_of_relative_links() {
+        for base in [
+            "https://pokeapi.co/",
+            "https://pokeapi.co/api/v2/",
+            "https://mirror.example/mirror/deep/path/",
+        ] {
+            assert_eq!(id(base, "pokemon", "pokemon/25/"), Some(25), "{base}");
+            assert_eq!(id(base, "pokemon", "pokemon/25"), Some(25), "{base}");
+            assert_eq!(id(base, "pokemon", "./pokemon/132/"), Some(132), "{base}");
+        }
+        // Root-relative links leave the base and only need to end with the kind and id
+        assert_eq!(
+            id(
+                "https://mirror.example/mirror/",
+                "pokemon",
+                "/api/v2/pokemon/7/"
+            ),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn resource_id_on_another_origin() {
+        let base = "https://mirror.example/mirror/deep/path/";
+        for url in [
+            "https://pokeapi.co/api/v2/pokemon/25/",
+            "https://pokeapi.co/api/v2/pokemon/25",
+            "http://mirror.example/mirror/deep/path/pokemon/25/",
+            "https://other.example/mirror/deep/path/extra/pokemon/25/",
+        ] {
+            assert_eq!(id(base, "pokemon", url), Some(25), "{url}");
+        }
+    }
+
+    #[test]
+    fn resource_id_of_wrong_kind() {
+        let base = "https://pokeapi.co/api/v2/";
+        assert_eq!(
+            id(
+                base,
+                "pokemon",
+                "https://pokeapi.co/api/v2/pokemon-species/25/"
+            ),
+            None
+        );
+        assert_eq!(
+            id(base, "pokemon", "https://pokeapi.co/api/v2/type/10/"),
+            None
+        );
+        assert_eq!(
+            id(base, "type", "https://elsewhere.example/pokemon/25/"),
+            None
+        );
+    }
+
+    #[test]
+    fn resource_id_with_extra_segments_under_base() {
+        let base = "https://pokeapi.co/api/v2/";
+        assert_eq!(
+            id(
+                base,
+                "pokemon",
+                "https://pokeapi.co/api/v2/extra/pokemon/25/"
+            ),
+            None
+        );
+        assert_eq!(id(base, "pokemon", "extra/pokemon/25/"), None);
+        let base = "https://mirror.example/mirror/deep/path/";
+        assert_eq!(
+            id(
+                base,
+                "pokemon",
+                "https://mirror.example/mirror/deep/path/a/pokemon/25"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn resource_id_without_numeric_id() {
+        let base = "https://pokeapi.co/api/v2/";
+        assert_eq!(
+            id(
+                base,
+                "pokemon",
+                "https://pokeapi.co/api/v2/pokemon/pikachu/"
+            ),
+            None
+        );
+        assert_eq!(
+            id(base, "pokemon", "https://pokeapi.co/api/v2/pokemon/"),
+            None
+        );
+        assert_eq!(id(base, "pokemon", "https://pokeapi.co/api/v2/"), None);
+        assert_eq!(
+            id(base, "pokemon", "https://pokeapi.co/api/v2/pokemon/-1/"),
+            None
+        );
+    }
+
+    /// Base url on pokeapi.co mounted at any path, with or without a trailing slash.
+    fn any_base() -> impl Strategy<Value = String> {
+        (vec("[a-z0-9-]{1,8}", 0..4), any::<bool>()).prop_map(|(segments, slash)| {
+            let path = segments.join("/");
+            match (path.is_empty(), slash) {
+                (true, _) => "https://pokeapi.co/".to_owned(),
+                (false, true) => format!("https://pokeapi.co/{path}/"),
+                (false, false) => format!("https://pokeapi.co/{path}"),
+            }
+        })
+    }
+
+    const KIND: &str = "[a-z]{1,8}(-[a-z]{1,8})?";
+
+    proptest! {
+        #[test]
+        fn resource_id_round_trips_under_any_base(
+            base in any_base(),
+            kind in KIND,
+            n: u32,
+            slash: bool,
+        ) {
+            let slash = if slash { "/" } else { "" };
+            let url = format!("{}/{kind}/{n}{slash}", base.trim_end_matches('/'));
+            prop_assert_eq!(id(&base, &kind, &url), Some(n));
+            prop_assert_eq!(id(&base, &kind, &format!("{kind}/{n}{slash}")), Some(n));
+        }
+
+        #[test]
+        fn resource_id_rejects_extra_segments_under_any_base(
+            base in any_base(),
+            extra in "[a-z0-9-]{1,8}",
+            kind in KIND,
+            n: u32,
+        ) {
+            let url = format!("{}/{extra}/{kind}/{n}/", base.trim_end_matches('/'));
+            prop_assert_eq!(id(&base, &kind, &url), None);
+        }
+
+        #[test]
+        fn resource_id_rejects_other_kinds(
+            base in any_base(),
+            kind in KIND,
+            other in KIND,
+            n: u32,
+        ) {
+            prop_assume!(kind != other);
+            let url = format!("{}/{other}/{n}/", base.trim_end_matches('/'));
+            prop_assert_eq!(id(&base, &kind, &url), None);
+        }
+
+        #[test]
+        fn resource_id_accepts_any_path_on_another_origin(
+            base in any_base(),
+            prefix in vec("[a-z0-9-]{1,8}", 0..4),
+            kind in KIND,
+            n: u32,
+        ) {
+            let prefix: String = prefix.iter().map(|s| format!("{s}/")).collect();
+            let url = format!("https://mirror.example/{prefix}{kind}/{n}/");
+            prop_assert_eq!(id(&base, &kind, &url), Some(n));
+        }
+    }
+
+    #[test]
+    fn resource_url_escapes_the_id() {
+        let pokedex = Pokedex::new("https://pokeapi.co/api/v2/").unwrap();
+        assert_eq!(
+            pokedex.resource_url("type", "fire").unwrap().as_str(),
+            "https://pokeapi.co/api/v2/type/fire"
+        );
+        assert_eq!(
+            pokedex.resource_url("type", "a/b").unwrap().as_str(),
+            "https://pokeapi.co/api/v2/type/a%2Fb"
+        );
+    }
+
+    #[test]
+    fn resource_url_rejects_ids_naming_other_paths() {
+        let pokedex = Pokedex::new("https://pokeapi.co/api/v2/").unwrap();
+        for id in ["", ".", ".."] {
+            assert!(matches!(
+                pokedex.resource_url("type", id),
+                Err(PokedexError::NotFound)
+            ));
+        }
+    }
+}
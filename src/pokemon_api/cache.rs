@@ -0,0 +1,230 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use super::validators::Validators;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MemoryCacheConfig {
+    /// Maximum number of responses kept in memory
+    pub capacity: usize,
+    /// How long a response is served from memory before it is fetched again
+    pub ttl: Duration,
+}
+
+impl Default for MemoryCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            ttl: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Bounded in-process cache of upstream response bodies keyed by request url.
+///
+/// Entries expire after the configured ttl; once the cache is full the least
+/// recently used entry is evicted. Expired entries upstream sent validators for are
+/// kept, so that they can be revalidated instead of downloaded again.
+pub(crate) struct MemoryCache {
+    config: MemoryCacheConfig,
+    state: Mutex<State>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, Entry>,
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+struct Entry {
+    body: Bytes,
+    validators: Validators,
+    stored_at: Instant,
+    used_at: u64,
+}
+
+impl State {
+    fn touch(&mut self, key: &str) -> Option<Bytes> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.used_at);
+        self.recency.insert(self.clock, key.to_owned());
+        entry.used_at = self.clock;
+        Some(entry.body.clone())
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used_at);
+        }
+    }
+}
+
+impl MemoryCache {
+    pub fn new(config: MemoryCacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Bytes> {
+        let mut state = self.state.lock().unwrap();
+        let fresh = state.entries.get(key).map(|e| {
+            (
+                e.stored_at.elapsed() < self.config.ttl,
+                e.validators.is_empty(),
+            )
+        });
+        let body = match fresh {
+            Some((true, _)) => state.touch(key),
+            Some((false, true)) => {
+                state.remove(key);
+                None
+            }
+            Some((false, false)) | None => None,
+        };
+        match body {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        body
+    }
+
+    /// Expired entry for `key` along with its validators, to revalidate with upstream.
+    pub fn get_stale(&self, key: &str) -> Option<(Bytes, Validators)> {
+        let state = self.state.lock().unwrap();
+        let entry = state.entries.get(key)?;
+        if entry.validators.is_empty() {
+            return None;
+        }
+        Some((entry.body.clone(), entry.validators.clone()))
+    }
+
+    pub fn insert(&self, key: String, body: Bytes, validators: Validators) {
+        if self.config.capacity == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.remove(&key);
+        while state.entries.len() >= self.config.capacity {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+        state.clock += 1;
+        let used_at = state.clock;
+        state.recency.insert(used_at, key.clone());
+        state.entries.insert(
+            key,
+            Entry {
+                body,
+                validators,
+                stored_at: Instant::now(),
+                used_at,
+            },
+        );
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.state.lock().unwrap().entries.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use super::*;
+
+    const TTL: Duration = Duration::from_millis(20);
+
+    fn cache() -> MemoryCache {
+        MemoryCache::new(MemoryCacheConfig {
+            capacity: 2,
+            ttl: TTL,
+        })
+    }
+
+    fn etag(value: &str) -> Validators {
+        Validators {
+            etag: Some(value.to_owned()),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn fresh_entries_are_served() {
+        let cache = cache();
+        cache.insert("a".to_owned(), Bytes::from("body"), Validators::default());
+        assert_eq!(cache.get("a"), Some(Bytes::from("body")));
+        assert_eq!(cache.get("b"), None);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+    }
+
+    #[test]
+    fn expired_entries_with_validators_are_kept_for_revalidation() {
+        let cache = cache();
+        cache.insert("a".to_owned(), Bytes::from("body"), etag("\"v1\""));
+        sleep(TTL * 2);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(
+            cache.get_stale("a"),
+            Some((Bytes::from("body"), etag("\"v1\"")))
+        );
+        assert_eq!(cache.stats().entries, 1);
+
+        // Refreshing after a 304 makes the entry fresh again
+        cache.insert("a".to_owned(), Bytes::from("body"), etag("\"v2\""));
+        assert_eq!(cache.get("a"), Some(Bytes::from("body")));
+    }
+
+    #[test]
+    fn expired_entries_without_validators_are_evicted() {
+        let cache = cache();
+        cache.insert("a".to_owned(), Bytes::from("body"), Validators::default());
+        assert_eq!(cache.get_stale("a"), None);
+        sleep(TTL * 2);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get_stale("a"), None);
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted_when_full() {
+        let cache = cache();
+        cache.insert("a".to_owned(), Bytes::from("a"), etag("\"a\""));
+        cache.insert("b".to_owned(), Bytes::from("b"), Validators::default());
+        assert!(cache.get("a").is_some());
+        cache.insert("c".to_owned(), Bytes::from("c"), Validators::default());
+        assert_eq!(cache.get("b"), None);
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CircuitBreakerConfig {
+    /// Consecutive failures after which the circuit opens
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before probing upstream again
+    pub open_duration: Duration,
+    /// Probe requests let through while half-open; that many successes close the circuit
+    pub half_open_max_calls: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            half_open_max_calls: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CircuitBreakerStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Time until the circuit lets probe requests through, while open
+    pub retry_after: Option<Duration>,
+}
+
+/// Fails upstream calls fast once upstream looks down instead of waiting on every request.
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    half_open_in_flight: u32,
+    half_open_successes: u32,
+}
+
+impl Inner {
+    fn open(&mut self) {
+        self.state = CircuitState::Open;
+        self.opened_at = Instant::now();
+    }
+
+    fn remaining_open(&self, config: &CircuitBreakerConfig) -> Duration {
+        config
+            .open_duration
+            .saturating_sub(self.opened_at.elapsed())
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+                half_open_in_flight: 0,
+                half_open_successes: 0,
+            }),
+        }
+    }
+
+    /// Asks for permission to call upstream; on refusal returns how long to wait.
+    pub fn acquire(&self) -> Result<CallPermit<'_>, Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state == CircuitState::Open {
+            let remaining = inner.remaining_open(&self.config);
+            if !remaining.is_zero() {
+                return Err(remaining);
+            }
+            inner.state = CircuitState::HalfOpen;
+            inner.half_open_in_flight = 0;
+            inner.half_open_successes = 0;
+        }
+        if inner.state == CircuitState::HalfOpen {
+            if inner.half_open_in_flight >= self.config.half_open_max_calls {
+                return Err(Duration::from_secs(1));
+            }
+            inner.half_open_in_flight += 1;
+        }
+        Ok(CallPermit {
+            breaker: self,
+            recorded: false,
+        })
+    }
+
+    fn on_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        if inner.state == CircuitState::HalfOpen {
+            inner.half_open_in_flight = inner.half_open_in_flight.saturating_sub(1);
+            inner.half_open_successes += 1;
+            if inner.half_open_successes >= self.config.half_open_max_calls {
+                inner.state = CircuitState::Closed;
+            }
+        }
+    }
+
+    fn on_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        match inner.state {
+            CircuitState::HalfOpen => inner.open(),
+            CircuitState::Closed if inner.consecutive_failures >= self.config.failure_threshold => {
+                inner.open()
+            }
+            _ => {}
+        }
+    }
+
+    fn on_abandoned(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state == CircuitState::HalfOpen {
+            inner.half_open_in_flight = inner.half_open_in_flight.saturating_sub(1);
+        }
+    }
+
+    pub fn status(&self) -> CircuitBreakerStatus {
+        let inner = self.inner.lock().unwrap();
+        CircuitBreakerStatus {
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            retry_after: (inner.state == CircuitState::Open)
+                .then(|| inner.remaining_open(&self.config)),
+        }
+    }
+}
+
+/// Outcome slot of a call let through by [`CircuitBreaker::acquire`].
+///
+/// Dropping it without recording an outcome (e.g. when the request future is
+/// cancelled) frees the slot without counting as success or failure.
+pub(crate) struct CallPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    recorded: bool,
+}
+
+impl CallPermit<'_> {
+    pub fn record_success(mut self) {
+        self.recorded = true;
+        self.breaker.on_success();
+    }
+
+    pub fn record_failure(mut self) {
+        self.recorded = true;
+        self.breaker.on_failure();
+    }
+}
+
+impl Drop for CallPermit<'_> {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.breaker.on_abandoned();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(open_duration: Duration, half_open_max_calls: u32) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 3,
+            open_duration,
+            half_open_max_calls,
+        })
+    }
+
+    fn fail(breaker: &CircuitBreaker, times: u32) {
+        for _ in 0..times {
+            breaker.acquire().unwrap().record_failure();
+        }
+    }
+
+    /// Opens the circuit of a breaker that lets probes through right away.
+    fn opened(half_open_max_calls: u32) -> CircuitBreaker {
+        let breaker = breaker(Duration::ZERO, half_open_max_calls);
+        fail(&breaker, 3);
+        assert_eq!(breaker.status().state, CircuitState::Open);
+        breaker
+    }
+
+    #[test]
+    fn opens_after_failure_threshold() {
+        let breaker = breaker(Duration::from_secs(60), 1);
+        fail(&breaker, 2);
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert_eq!(breaker.status().consecutive_failures, 2);
+        fail(&breaker, 1);
+        assert_eq!(breaker.status().state, CircuitState::Open);
+    }
+
+    #[test]
+    fn success_resets_consecutive_failures() {
+        let breaker = breaker(Duration::from_secs(60), 1);
+        fail(&breaker, 2);
+        breaker.acquire().unwrap().record_success();
+        fail(&breaker, 2);
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert_eq!(breaker.status().consecutive_failures, 2);
+    }
+
+    #[test]
+    fn rejects_with_remaining_open_time() {
+        let breaker = breaker(Duration::from_secs(60), 1);
+        fail(&breaker, 3);
+        let remaining = breaker.acquire().err().unwrap();
+        assert!(remaining > Duration::from_secs(59) && remaining <= Duration::from_secs(60));
+        let retry_after = breaker.status().retry_after.unwrap();
+        assert!(retry_after <= remaining);
+    }
+
+    #[test]
+    fn half_open_lets_a_single_probe_through() {
+        let breaker = opened(1);
+        let probe = breaker.acquire().unwrap();
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+        assert_eq!(breaker.acquire().err(), Some(Duration::from_secs(1)));
+        probe.record_success();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert_eq!(breaker.status().consecutive_failures, 0);
+        let _ = breaker.acquire().unwrap();
+        let _ = breaker.acquire().unwrap();
+    }
+
+    #[test]
+    fn half_open_closes_after_enough_successful_probes() {
+        let breaker = opened(2);
+        let (first, second) = (breaker.acquire().unwrap(), breaker.acquire().unwrap());
+        assert!(breaker.acquire().is_err());
+        first.record_success();
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+        second.record_success();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+    }
+
+    #[test]
+    fn probe_failure_reopens_the_circuit() {
+        let breaker = opened(1);
+        let probe = breaker.acquire().unwrap();
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+        probe.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::Open);
+        assert_eq!(breaker.status().consecutive_failures, 4);
+    }
+
+    #[test]
+    fn abandoned_permit_frees_its_slot() {
+        let breaker = opened(1);
+        drop(breaker.acquire().unwrap());
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+        assert_eq!(breaker.status().consecutive_failures, 3);
+        breaker.acquire().unwrap().record_success();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use reqwest::Url;
+use tokio::fs;
+use tracing::warn;
+
+use super::validators::Validators;
+
+#[derive(Debug, Clone)]
+pub(crate) struct DiskCacheConfig {
+    /// Directory holding one JSON file per upstream response
+    pub dir: PathBuf,
+    /// Entries older than this are ignored and fetched again
+    pub max_age: Duration,
+}
+
+/// Directory of upstream response bodies that survives restarts.
+///
+/// Files mirror the resource paths relative to the base url, e.g.
+/// `pokemon/25/index.json` or `pokemon/index_limit=20&offset=0.json`, so the
+/// cache can be pre-populated and inspected with ordinary tools. Validators upstream
+/// sent with a response are kept next to it, e.g. `pokemon/25/index.validators.json`.
+pub(crate) struct DiskCache {
+    config: DiskCacheConfig,
+}
+
+impl DiskCache {
+    pub fn new(config: DiskCacheConfig) -> Self {
+        Self { config }
+    }
+
+    /// Location of the cache entry for `url`, or `None` if it does not belong under `base`.
+    pub fn path_for(&self, base: &Url, url: &Url) -> Option<PathBuf> {
+        if url.origin() != base.origin() {
+            return None;
+        }
+        let relative = url.path().strip_prefix(base.path())?;
+        let mut path = self.config.dir.clone();
+        for segment in relative.split('/').filter(|s| !s.is_empty()) {
+            if segment == "." || segment == ".." {
+                return None;
+            }
+            path.push(segment);
+        }
+        match url.query() {
+            Some(query) => path.push(format!("index_{}.json", query.replace('/', "%2F"))),
+            None => path.push("index.json"),
+        }
+        Some(path)
+    }
+
+    pub async fn get(&self, path: &Path) -> Option<(Bytes, Validators)> {
+        let metadata = fs::metadata(path).await.ok()?;
+        let age = metadata.modified().ok()?.elapsed().unwrap_or_default();
+        if age > self.config.max_age {
+            return None;
+        }
+        let body = fs::read(path).await.ok().map(Bytes::from)?;
+        Some((body, Self::validators(path).await))
+    }
+
+    /// Entry at `path` whatever its age, if it can be revalidated with upstream.
+    pub async fn get_stale(&self, path: &Path) -> Option<(Bytes, Validators)> {
+        let validators = Self::validators(path).await;
+        if validators.is_empty() {
+            return None;
+        }
+        let body = fs::read(path).await.ok().map(Bytes::from)?;
+        Some((body, validators))
+    }
+
+    /// Also used to refresh an entry upstream confirmed unchanged, resetting its age.
+    pub async fn insert(
+        &self,
+        path: &Path,
+        body: &[u8],
+        validators: &Validators,
+    ) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let validators_path = Self::validators_path(path);
+        if validators.is_empty() {
+            match fs::remove_file(&validators_path).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        } else {
+            let json = serde_json::to_vec(validators).map_err(io::Error::from)?;
+            Self::write(&validators_path, &json).await?;
+        }
+        Self::write(path, body).await
+    }
+
+    async fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, contents).await?;
+        fs::rename(&tmp, path).await
+    }
+
+    fn validators_path(path: &Path) -> PathBuf {
+        path.with_extension("validators.json")
+    }
+
+    /// Validators stored next to the entry at `path`, empty if there are none.
+    async fn validators(path: &Path) -> Validators {
+        let validators_path = Self::validators_path(path);
+        let Ok(json) = fs::read(&validators_path).await else {
+            return Validators::default();
+        };
+        serde_json::from_slice(&json).unwrap_or_else(|e| {
+            warn!(err = %e, path = %validators_path.display(), "ignoring unreadable validators");
+            Validators::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory for one test, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("pokedex-disk-cache-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn cache(dir: &TempDir, max_age: Duration) -> DiskCache {
+        DiskCache::new(DiskCacheConfig {
+            dir: dir.0.clone(),
+            max_age,
+        })
+    }
+
+    fn validators() -> Validators {
+        Validators {
+            etag: Some("W/\"abc\"".to_owned()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+        }
+    }
+
+    #[test]
+    fn path_for_mirrors_resource_paths() {
+        let dir = TempDir::new("paths");
+        let cache = cache(&dir, Duration::from_secs(60));
+        let base = Url::parse("https://pokeapi.co/api/v2/").unwrap();
+        let url = base.join("pokemon/25/").unwrap();
+        assert_eq!(
+            cache.path_for(&base, &url),
+            Some(dir.0.join("pokemon/25/index.json"))
+        );
+        let url = base.join("pokemon?limit=20&offset=0").unwrap();
+        assert_eq!(
+            cache.path_for(&base, &url),
+            Some(dir.0.join("pokemon/index_limit=20&offset=0.json"))
+        );
+        let url = Url::parse("https://elsewhere.example/api/v2/pokemon/25/").unwrap();
+        assert_eq!(cache.path_for(&base, &url), None);
+    }
+
+    #[tokio::test]
+    async fn insert_keeps_validators_next_to_the_body() {
+        let dir = TempDir::new("validators");
+        let cache = cache(&dir, Duration::from_secs(60));
+        let path = dir.0.join("pokemon/25/index.json");
+        cache.insert(&path, b"{}", &validators()).await.unwrap();
+        assert!(dir.0.join("pokemon/25/index.validators.json").exists());
+        assert_eq!(
+            cache.get(&path).await,
+            Some((Bytes::from("{}"), validators()))
+        );
+        assert_eq!(
+            cache.get_stale(&path).await,
+            Some((Bytes::from("{}"), validators()))
+        );
+    }
+
+    #[tokio::test]
+    async fn insert_without_validators_removes_stored_ones() {
+        let dir = TempDir::new("removal");
+        let cache = cache(&dir, Duration::from_secs(60));
+        let path = dir.0.join("type/index.json");
+        cache.insert(&path, b"old", &validators()).await.unwrap();
+        cache
+            .insert(&path, b"new", &Validators::default())
+            .await
+            .unwrap();
+        assert!(!dir.0.join("type/index.validators.json").exists());
+        assert_eq!(
+            cache.get(&path).await,
+            Some((Bytes::from("new"), Validators::default()))
+        );
+        assert_eq!(cache.get_stale(&path).await, None);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_only_served_stale() {
+        let dir = TempDir::new("expiry");
+        let cache = cache(&dir, Duration::from_millis(1));
+        let path = dir.0.join("pokemon/index.json");
+        cache.insert(&path, b"{}", &validators()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(cache.get(&path).await, None);
+        assert_eq!(
+            cache.get_stale(&path).await,
+            Some((Bytes::from("{}"), validators()))
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Typed models of the upstream PokeAPI resources.
+//!
+//! Field names follow the upstream JSON, see <https://pokeapi.co/docs/v2>. Fields
+//! upstream adds later are ignored, so only the fields some endpoint needs, or is
+//! likely to, are modelled; they are serializable back for caching and fixtures.
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use super::{resource_id, PokedexError};
+
+/// Link to another upstream resource, as found in lists and inside other resources.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NamedApiResource {
+    pub url: String,
+    pub name: String,
+}
+
+impl NamedApiResource {
+    /// Id of the linked resource, which must be of `kind`; see [`resource_id`].
+    pub fn id(&self, base: &Url, kind: &str) -> Result<u32, PokedexError> {
+        resource_id(base, kind, &self.url)
+    }
+}
+
+/// Link to another upstream resource that has no name.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiResource {
+    pub url: String,
+}
+
+impl ApiResource {
+    /// Id of the linked resource, which must be of `kind`; see [`resource_id`].
+    pub fn id(&self, base: &Url, kind: &str) -> Result<u32, PokedexError> {
+        resource_id(base, kind, &self.url)
+    }
+}
+
+/// One page of a resource collection, e.g. `pokemon?limit=20&offset=40`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ResourceList<R> {
+    pub count: u32,
+    /// Url of the next page, if any
+    pub next: Option<String>,
+    pub previous: Option<String>,
+    pub results: Vec<R>,
+}
+
+/// Page of the usual collections, which link to their resources.
+pub type NamedApiResourceList = ResourceList<NamedApiResource>;
+
+/// Text of an effect in one language, at full length and abridged.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VerboseEffect {
+    pub effect: String,
+    pub short_effect: String,
+    pub language: NamedApiResource,
+}
+
+/// `pokemon/{id or name}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonDetail {
+    pub id: u32,
+    pub name: String,
+    pub height: u32,
+    pub weight: u32,
+    pub base_experience: Option<u32>,
+    pub types: Vec<PokemonTypeSlot>,
+    pub abilities: Vec<PokemonAbilitySlot>,
+    pub stats: Vec<PokemonStat>,
+    pub sprites: PokemonSprites,
+    pub species: NamedApiResource,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonTypeSlot {
+    pub slot: u32,
+    #[serde(rename = "type")]
+    pub type_: NamedApiResource,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonAbilitySlot {
+    pub slot: u32,
+    pub is_hidden: bool,
+    pub ability: NamedApiResource,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonStat {
+    pub base_stat: u32,
+    pub effort: u32,
+    pub stat: NamedApiResource,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonSprites {
+    pub front_default: Option<String>,
+    pub front_shiny: Option<String>,
+    pub back_default: Option<String>,
+    pub back_shiny: Option<String>,
+}
+
+/// `pokemon-species/{id or name}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonSpecies {
+    pub id: u32,
+    pub name: String,
+    pub genera: Vec<Genus>,
+    pub flavor_text_entries: Vec<FlavorText>,
+    pub capture_rate: u32,
+    pub habitat: Option<NamedApiResource>,
+    pub color: NamedApiResource,
+    pub is_legendary: bool,
+    pub is_mythical: bool,
+    pub varieties: Vec<PokemonSpeciesVariety>,
+    pub evolution_chain: Option<ApiResource>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Genus {
+    pub genus: String,
+    pub language: NamedApiResource,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FlavorText {
+    pub flavor_text: String,
+    pub language: NamedApiResource,
+    pub version: Option<NamedApiResource>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonSpeciesVariety {
+    pub is_default: bool,
+    pub pokemon: NamedApiResource,
+}
+
+/// `evolution-chain/{id}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EvolutionChain {
+    pub id: u32,
+    pub chain: ChainLink,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChainLink {
+    pub is_baby: bool,
+    pub species: NamedApiResource,
+    pub evolution_details: Vec<EvolutionDetail>,
+    pub evolves_to: Vec<ChainLink>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EvolutionDetail {
+    pub trigger: NamedApiResource,
+    pub min_level: Option<u32>,
+    pub item: Option<NamedApiResource>,
+    pub held_item: Option<NamedApiResource>,
+    pub min_happiness: Option<u32>,
+    pub min_affection: Option<u32>,
+    pub min_beauty: Option<u32>,
+    #[serde(default)]
+    pub time_of_day: String,
+    pub known_move: Option<NamedApiResource>,
+    pub known_move_type: Option<NamedApiResource>,
+    pub location: Option<NamedApiResource>,
+    pub trade_species: Option<NamedApiResource>,
+    pub gender: Option<u32>,
+    #[serde(default)]
+    pub needs_overworld_rain: bool,
+    #[serde(default)]
+    pub turn_upside_down: bool,
+}
+
+/// `type/{id or name}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonType {
+    pub id: u32,
+    pub name: String,
+    pub damage_relations: TypeRelations,
+    pub generation: NamedApiResource,
+    pub move_damage_class: Option<NamedApiResource>,
+    pub pokemon: Vec<TypePokemon>,
+    #[serde(default)]
+    pub moves: Vec<NamedApiResource>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TypeRelations {
+    pub no_damage_to: Vec<NamedApiResource>,
+    pub half_damage_to: Vec<NamedApiResource>,
+    pub double_damage_to: Vec<NamedApiResource>,
+    pub no_damage_from: Vec<NamedApiResource>,
+    pub half_damage_from: Vec<NamedApiResource>,
+    pub double_damage_from: Vec<NamedApiResource>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TypePokemon {
+    pub slot: u32,
+    pub pokemon: NamedApiResource,
+}
+
+/// `ability/{id or name}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Ability {
+    pub id: u32,
+    pub name: String,
+    pub is_main_series: bool,
+    pub generation: NamedApiResource,
+    #[serde(default)]
+    pub effect_entries: Vec<VerboseEffect>,
+    pub pokemon: Vec<AbilityPokemon>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AbilityPokemon {
+    pub is_hidden: bool,
+    pub slot: u32,
+    pub pokemon: NamedApiResource,
+}
+
+/// `move/{id or name}`
+// Not served by any endpoint yet
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Move {
+    pub id: u32,
+    pub name: String,
+    pub accuracy: Option<u32>,
+    pub effect_chance: Option<u32>,
+    pub pp: Option<u32>,
+    pub priority: i32,
+    pub power: Option<u32>,
+    pub damage_class: Option<NamedApiResource>,
+    #[serde(rename = "type")]
+    pub type_: NamedApiResource,
+    pub generation: NamedApiResource,
+    #[serde(default)]
+    pub effect_entries: Vec<VerboseEffect>,
+    #[serde(default)]
+    pub learned_by_pokemon: Vec<NamedApiResource>,
+}
+
+/// `item/{id or name}`
+// Not served by any endpoint yet, like the item parts below
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Item {
+    pub id: u32,
+    pub name: String,
+    pub cost: u32,
+    pub fling_power: Option<u32>,
+    pub fling_effect: Option<NamedApiResource>,
+    #[serde(default)]
+    pub attributes: Vec<NamedApiResource>,
+    pub category: NamedApiResource,
+    #[serde(default)]
+    pub effect_entries: Vec<VerboseEffect>,
+    pub sprites: ItemSprites,
+    #[serde(default)]
+    pub held_by_pokemon: Vec<ItemHolderPokemon>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ItemSprites {
+    pub default: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ItemHolderPokemon {
+    pub pokemon: NamedApiResource,
+}
+
+/// `generation/{id or name}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Generation {
+    pub id: u32,
+    pub name: String,
+    pub main_region: NamedApiResource,
+    pub pokemon_species: Vec<NamedApiResource>,
+    #[serde(default)]
+    pub types: Vec<NamedApiResource>,
+    #[serde(default)]
+    pub moves: Vec<NamedApiResource>,
+    #[serde(default)]
+    pub abilities: Vec<NamedApiResource>,
+}
+
+/// `pokemon-habitat/{id or name}`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PokemonHabitat {
+    pub id: u32,
+    pub name: String,
+    pub pokemon_species: Vec<NamedApiResource>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Upstream response excerpt from `tests/fixtures/pokeapi`.
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/pokeapi/",
+                $name,
+                ".json"
+            ))
+        };
+    }
+
+    /// Deserializes `json` and checks that serializing it back and deserializing again
+    /// gives the same model, and that the fixture has fields the model ignores.
+    fn round_trip<T: DeserializeOwned + Serialize>(json: &str) -> T {
+        let model: T = serde_json::from_str(json).expect("fixture deserializes");
+        let serialized = serde_json::to_value(&model).unwrap();
+        let again: T = serde_json::from_value(serialized.clone()).expect("model deserializes");
+        assert_eq!(serde_json::to_value(&again).unwrap(), serialized);
+
+        let raw: Value = serde_json::from_str(json).unwrap();
+        let unknown = raw
+            .as_object()
+            .unwrap()
+            .keys()
+            .filter(|key| serialized.get(key.as_str()).is_none())
+            .count();
+        assert!(unknown > 0, "fixture has no fields unknown to the model");
+        model
+    }
+
+    #[test]
+    fn pokemon_round_trips() {
+        let p: PokemonDetail = round_trip(fixture!("pokemon"));
+        assert_eq!((p.id, p.name.as_str()), (132, "ditto"));
+        assert_eq!(p.stats.iter().map(|s| s.base_stat).sum::<u32>(), 288);
+        assert_eq!(p.abilities[1].ability.name, "imposter");
+        assert!(p.abilities[1].is_hidden);
+        assert_eq!(p.types[0].type_.name, "normal");
+    }
+
+    #[test]
+    fn species_round_trips() {
+        let s: PokemonSpecies = round_trip(fixture!("pokemon-species"));
+        assert_eq!((s.id, s.name.as_str()), (133, "eevee"));
+        assert_eq!(s.habitat.unwrap().name, "urban");
+        assert_eq!(
+            s.evolution_chain.unwrap().url,
+            "https://pokeapi.co/api/v2/evolution-chain/67/"
+        );
+        assert_eq!(s.varieties.len(), 2);
+    }
+
+    #[test]
+    fn evolution_chain_round_trips() {
+        let c: EvolutionChain = round_trip(fixture!("evolution-chain"));
+        assert_eq!(c.id, 67);
+        assert_eq!(c.chain.species.name, "eevee");
+        assert_eq!(c.chain.evolves_to.len(), 3);
+        let espeon = &c.chain.evolves_to[1].evolution_details[0];
+        assert_eq!(espeon.min_happiness, Some(160));
+        assert_eq!(espeon.time_of_day, "day");
+    }
+
+    #[test]
+    fn type_round_trips() {
+        let t: PokemonType = round_trip(fixture!("type"));
+        assert_eq!((t.id, t.name.as_str()), (18, "fairy"));
+        assert_eq!(t.damage_relations.no_damage_from[0].name, "dragon");
+        assert!(t.move_damage_class.is_none());
+        assert_eq!(t.pokemon.len(), 3);
+    }
+
+    #[test]
+    fn ability_round_trips() {
+        let a: Ability = round_trip(fixture!("ability"));
+        assert_eq!((a.id, a.name.as_str()), (150, "imposter"));
+        assert!(a.pokemon[0].is_hidden);
+        assert_eq!(
+            a.effect_entries[0].short_effect,
+            "Transforms upon entering battle."
+        );
+    }
+
+    #[test]
+    fn move_round_trips() {
+        let m: Move = round_trip(fixture!("move"));
+        assert_eq!((m.id, m.name.as_str()), (85, "thunderbolt"));
+        assert_eq!((m.power, m.pp, m.accuracy), (Some(90), Some(15), Some(100)));
+        assert_eq!(m.type_.name, "electric");
+    }
+
+    #[test]
+    fn item_round_trips() {
+        let i: Item = round_trip(fixture!("item"));
+        assert_eq!((i.id, i.name.as_str()), (213, "light-ball"));
+        assert_eq!(i.fling_power, Some(30));
+        assert_eq!(i.held_by_pokemon[0].pokemon.name, "pikachu");
+    }
+
+    #[test]
+    fn generation_round_trips() {
+        let g: Generation = round_trip(fixture!("generation"));
+        assert_eq!((g.id, g.name.as_str()), (1, "generation-i"));
+        assert_eq!(g.main_region.name, "kanto");
+        assert!(g.abilities.is_empty());
+        assert_eq!(g.pokemon_species.len(), 4);
+    }
+
+    #[test]
+    fn habitat_round_trips() {
+        let h: PokemonHabitat = round_trip(fixture!("pokemon-habitat"));
+        assert_eq!((h.id, h.name.as_str()), (8, "urban"));
+        assert_eq!(h.pokemon_species.len(), 3);
+    }
+}
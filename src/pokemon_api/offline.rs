@@ -0,0 +1,204 @@
+use std::{io, path::PathBuf};
+
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use tokio::{fs, sync::OnceCell};
+use tracing::instrument;
+
+use super::{
+    Ability, EvolutionChain, Generation, NamedApiResource, NamedApiResourceList, PokedexBackend,
+    PokedexError, PokemonDetail, PokemonHabitat, PokemonSpecies, PokemonType,
+};
+
+/// Backend serving everything from a local PokeAPI data dump.
+///
+/// `root` mirrors the resource paths under the base url in the `api-data`
+/// layout, e.g. `<root>/pokemon/index.json` and `<root>/pokemon/25/index.json`.
+/// Relative urls found in the dump are resolved against `base` so responses look
+/// the same as the ones served by [`super::Pokedex`].
+pub(crate) struct LocalPokedex {
+    root: PathBuf,
+    base: Url,
+    pokemon_index: OnceCell<Vec<NamedApiResource>>,
+}
+
+impl LocalPokedex {
+    pub fn new(root: impl Into<PathBuf>, base: &str) -> Result<Self, PokedexError> {
+        let base = Url::try_from(base).map_err(|_| PokedexError::InvalidBaseUrl)?;
+        if base.cannot_be_a_base() {
+            return Err(PokedexError::InvalidBaseUrl);
+        }
+        Ok(Self {
+            root: root.into(),
+            base,
+            pokemon_index: OnceCell::new(),
+        })
+    }
+
+    async fn read<T: DeserializeOwned>(&self, resource: &[&str]) -> Result<T, PokedexError> {
+        let mut path = self.root.clone();
+        for segment in resource {
+            if segment.is_empty()
+                || *segment == "."
+                || *segment == ".."
+                || segment.contains(['/', '\\'])
+            {
+                return Err(PokedexError::NotFound);
+            }
+            path.push(segment);
+        }
+        path.push("index.json");
+        let body = match fs::read(&path).await {
+            Ok(body) => body,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(PokedexError::NotFound),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Reads `<kind>/<name>`, resolving the name to an id through `<kind>/index.json`
+    /// since the dump only has directories per id.
+    async fn read_by_name<T: DeserializeOwned>(
+        &self,
+        kind: &str,
+        name: &str,
+    ) -> Result<T, PokedexError> {
+        match self.read(&[kind, name]).await {
+            Err(PokedexError::NotFound) => {}
+            result => return result,
+        }
+        let list: NamedApiResourceList = self.read(&[kind]).await?;
+        let entry = list
+            .results
+            .iter()
+            .find(|r| r.name == name)
+            .ok_or(PokedexError::NotFound)?;
+        let id = entry.id(&self.base, kind)?;
+        self.read(&[kind, id.to_string().as_str()]).await
+    }
+
+    fn absolute(&self, url: &str) -> String {
+        self.base
+            .join(url)
+            .map(String::from)
+            .unwrap_or_else(|_| url.to_owned())
+    }
+
+    fn page_url(&self, limit: u32, offset: usize) -> String {
+        let mut url = self
+            .base
+            .join("pokemon")
+            .expect("could not join with base url");
+        url.query_pairs_mut()
+            .append_pair("offset", &offset.to_string())
+            .append_pair("limit", &limit.to_string());
+        url.into()
+    }
+
+    async fn pokemon_index(&self) -> Result<&[NamedApiResource], PokedexError> {
+        let index = self
+            .pokemon_index
+            .get_or_try_init(|| async {
+                let list: NamedApiResourceList = self.read(&["pokemon"]).await?;
+                Ok::<_, PokedexError>(
+                    list.results
+                        .into_iter()
+                        .map(|p| NamedApiResource {
+                            url: self.absolute(&p.url),
+                            name: p.name,
+                        })
+                        .collect(),
+                )
+            })
+            .await?;
+        Ok(index)
+    }
+}
+
+#[async_trait]
+impl PokedexBackend for LocalPokedex {
+    fn base_url(&self) -> &Url {
+        &self.base
+    }
+
+    #[instrument(skip(self), err)]
+    async fn list_pokemon(
+        &self,
+        limit: u32,
+        offset: u32,
+    ) -> Result<NamedApiResourceList, PokedexError> {
+        let index = self.pokemon_index().await?;
+        let start = (offset as usize).min(index.len());
+        let end = start.saturating_add(limit as usize).min(index.len());
+        Ok(NamedApiResourceList {
+            count: index.len() as u32,
+            next: (end < index.len()).then(|| self.page_url(limit, end)),
+            previous: (start > 0)
+                .then(|| self.page_url(limit, start.saturating_sub(limit as usize))),
+            results: index[start..end].to_vec(),
+        })
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_pokemon_by_id(&self, id: u32) -> Result<PokemonDetail, PokedexError> {
+        self.read(&["pokemon", id.to_string().as_str()]).await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_pokemon_by_name(&self, name: &str) -> Result<PokemonDetail, PokedexError> {
+        match self.read(&["pokemon", name]).await {
+            Err(PokedexError::NotFound) => {}
+            result => return result,
+        }
+        // The dump only has directories per id, so resolve the name through the list index
+        let index = self.pokemon_index().await?;
+        let entry = index
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or(PokedexError::NotFound)?;
+        let id = entry.id(&self.base, "pokemon")?;
+        self.read(&["pokemon", id.to_string().as_str()]).await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_species(&self, id: u32) -> Result<PokemonSpecies, PokedexError> {
+        self.read(&["pokemon-species", id.to_string().as_str()])
+            .await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_evolution_chain(&self, id: u32) -> Result<EvolutionChain, PokedexError> {
+        self.read(&["evolution-chain", id.to_string().as_str()])
+            .await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn list_types(&self) -> Result<NamedApiResourceList, PokedexError> {
+        let mut list: NamedApiResourceList = self.read(&["type"]).await?;
+        for t in &mut list.results {
+            t.url = self.absolute(&t.url);
+        }
+        Ok(list)
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_type(&self, id_or_name: &str) -> Result<PokemonType, PokedexError> {
+        self.read_by_name("type", id_or_name).await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_ability(&self, id_or_name: &str) -> Result<Ability, PokedexError> {
+        self.read_by_name("ability", id_or_name).await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_generation(&self, id_or_name: &str) -> Result<Generation, PokedexError> {
+        self.read_by_name("generation", id_or_name).await
+    }
+
+    #[instrument(skip(self), err)]
+    async fn get_habitat(&self, id_or_name: &str) -> Result<PokemonHabitat, PokedexError> {
+        self.read_by_name("pokemon-habitat", id_or_name).await
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+use super::PokedexError;
+
+#[derive(Debug, Clone)]
+pub(crate) struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Factor the backoff grows by after every attempt
+    pub multiplier: f64,
+    /// Fraction of the backoff that is randomly taken off, between 0 and 1
+    pub jitter: f64,
+    pub retryable_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.5,
+            retryable_statuses: vec![408, 429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before the next attempt, or `None` if `error` after `attempt`
+    /// should be returned to the caller.
+    pub fn delay_for(&self, error: &PokedexError, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        match error {
+            PokedexError::HttpRequestError(e) if e.is_timeout() || e.is_connect() => {
+                Some(self.backoff(attempt))
+            }
+            PokedexError::UnexpectedStatus {
+                status,
+                retry_after,
+            } if self.retryable_statuses.contains(&status.as_u16()) => match retry_after {
+                // Waiting longer than we are ever willing to back off is pointless
+                Some(delay) if *delay > self.max_backoff => None,
+                Some(delay) => Some(*delay),
+                None => Some(self.backoff(attempt)),
+            },
+            _ => None,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self.initial_backoff.as_secs_f64()
+            * self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        let capped = exponential.min(self.max_backoff.as_secs_f64());
+        let jitter = rand::thread_rng().gen_range(0.0..=self.jitter);
+        Duration::from_secs_f64(capped * (1.0 - jitter))
+    }
+}
+
+/// Parses a `Retry-After` header given either in seconds or as an HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use tokio::sync::watch;
+
+use super::PokedexError;
+
+type Outcome = Result<Bytes, Arc<PokedexError>>;
+
+/// Coalesces concurrent loads of the same key: the first caller runs the load, callers
+/// arriving while it is in flight wait for it and share its result.
+pub(crate) struct SingleFlight {
+    in_flight: Mutex<HashMap<String, watch::Receiver<Option<Outcome>>>>,
+}
+
+enum Role {
+    Leader(watch::Sender<Option<Outcome>>),
+    Follower(watch::Receiver<Option<Outcome>>),
+}
+
+/// Ends the flight of `key` when the leader finishes or is dropped mid-flight.
+struct Flight<'a> {
+    flights: &'a SingleFlight,
+    key: &'a str,
+}
+
+impl Drop for Flight<'_> {
+    fn drop(&mut self) {
+        self.flights
+            .in_flight
+            .lock()
+            .expect("single flight lock poisoned")
+            .remove(self.key);
+    }
+}
+
+impl SingleFlight {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run<F>(&self, key: &str, load: F) -> Result<Bytes, PokedexError>
+    where
+        F: Future<Output = Result<Bytes, PokedexError>>,
+    {
+        loop {
+            let role = {
+                let mut in_flight = self.in_flight.lock().expect("single flight lock poisoned");
+                match in_flight.get(key) {
+                    Some(rx) => Role::Follower(rx.clone()),
+                    None => {
+                        let (tx, rx) = watch::channel(None);
+                        in_flight.insert(key.to_owned(), rx);
+                        Role::Leader(tx)
+                    }
+                }
+            };
+            match role {
+                Role::Leader(tx) => {
+                    let _flight = Flight { flights: self, key };
+                    let outcome = load.await.map_err(Arc::new);
+                    let result = share(&outcome);
+                    tx.send_replace(Some(outcome));
+                    return result;
+                }
+                Role::Follower(mut rx) => loop {
+                    if let Some(outcome) = rx.borrow_and_update().as_ref() {
+                        return share(outcome);
+                    }
+                    if rx.changed().await.is_err() {
+                        // The leader was dropped before finishing; try again, maybe as leader
+                        break;
+                    }
+                },
+            }
+        }
+    }
+}
+
+fn share(outcome: &Outcome) -> Result<Bytes, PokedexError> {
+    match outcome {
+        Ok(body) => Ok(body.clone()),
+        Err(e) => Err(PokedexError::shared(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::{future::join_all, poll};
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    const BODY: Bytes = Bytes::from_static(b"{}");
+
+    /// Runs `load` for `callers` concurrent callers of the same key; the load yields once
+    /// so that every caller arrives while it is in flight.
+    async fn run_concurrently(
+        flights: &SingleFlight,
+        callers: usize,
+        calls: &AtomicUsize,
+        load: impl Fn() -> Result<Bytes, PokedexError>,
+    ) -> Vec<Result<Bytes, PokedexError>> {
+        join_all((0..callers).map(|_| {
+            flights.run("pokemon/25", async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                load()
+            })
+        }))
+        .await
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_load() {
+        let (flights, calls) = (SingleFlight::new(), AtomicUsize::new(0));
+        let results = run_concurrently(&flights, 8, &calls, || Ok(BODY)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Ok(body) if *body == BODY)));
+        // Finished flights are not cached
+        run_concurrently(&flights, 1, &calls, || Ok(BODY)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn followers_share_the_leaders_error() {
+        let (flights, calls) = (SingleFlight::new(), AtomicUsize::new(0));
+        let results = run_concurrently(&flights, 3, &calls, || {
+            Err(PokedexError::UnexpectedStatus {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None,
+            })
+        })
+        .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        for result in results {
+            assert!(matches!(
+                result,
+                Err(PokedexError::UnexpectedStatus {
+                    status: StatusCode::SERVICE_UNAVAILABLE,
+                    ..
+                })
+            ));
+        }
+
+        let results = run_concurrently(&flights, 3, &calls, || {
+            Err(serde_json::from_slice::<()>(b"{").unwrap_err().into())
+        })
+        .await;
+        for result in results {
+            let Err(PokedexError::Coalesced(e)) = result else {
+                panic!("expected a coalesced error, got {result:?}");
+            };
+            assert!(matches!(*e, PokedexError::InvalidResponseBody(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn follower_takes_over_from_a_cancelled_leader() {
+        let flights = SingleFlight::new();
+        let mut leader = Box::pin(flights.run("pokemon/25", std::future::pending()));
+        let mut follower = Box::pin(flights.run("pokemon/25", async { Ok(BODY) }));
+        assert!(poll!(&mut leader).is_pending());
+        assert!(poll!(&mut follower).is_pending());
+        drop(leader);
+        assert!(matches!(follower.await, Ok(body) if body == BODY));
+        assert!(flights.in_flight.lock().unwrap().is_empty());
+    }
+}
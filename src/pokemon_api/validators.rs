@@ -0,0 +1,121 @@
+use reqwest::{
+    header::{HeaderMap, HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    RequestBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+/// Validators upstream sent along with a response, used to ask later whether it changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// These validators, updated with the ones `newer` has, e.g. from a 304 response.
+    pub fn merge(self, newer: Validators) -> Self {
+        Self {
+            etag: newer.etag.or(self.etag),
+            last_modified: newer.last_modified.or(self.last_modified),
+        }
+    }
+
+    /// Makes `request` conditional, so that upstream answers 304 if the response is unchanged.
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn sent_headers(request: RequestBuilder) -> HeaderMap {
+        request.build().unwrap().headers().clone()
+    }
+
+    fn get() -> RequestBuilder {
+        reqwest::Client::new().get("https://pokeapi.co/api/v2/pokemon/25/")
+    }
+
+    #[test]
+    fn apply_sets_conditional_headers() {
+        let validators = Validators {
+            etag: Some("\"abc\"".to_owned()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+        };
+        let headers = sent_headers(validators.apply(get()));
+        assert_eq!(headers[IF_NONE_MATCH], "\"abc\"");
+        assert_eq!(headers[IF_MODIFIED_SINCE], "Wed, 21 Oct 2015 07:28:00 GMT");
+    }
+
+    #[test]
+    fn apply_skips_missing_validators() {
+        let etag_only = Validators {
+            etag: Some("\"abc\"".to_owned()),
+            last_modified: None,
+        };
+        let headers = sent_headers(etag_only.apply(get()));
+        assert_eq!(headers[IF_NONE_MATCH], "\"abc\"");
+        assert!(headers.get(IF_MODIFIED_SINCE).is_none());
+        assert!(sent_headers(Validators::default().apply(get())).is_empty());
+    }
+
+    #[test]
+    fn from_headers_reads_etag_and_last_modified() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("W/\"abc\""));
+        assert_eq!(
+            Validators::from_headers(&headers),
+            Validators {
+                etag: Some("W/\"abc\"".to_owned()),
+                last_modified: None,
+            }
+        );
+        assert!(Validators::from_headers(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn merge_prefers_newer_validators() {
+        let stale = Validators {
+            etag: Some("\"v1\"".to_owned()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+        };
+        let newer = Validators {
+            etag: Some("\"v2\"".to_owned()),
+            last_modified: None,
+        };
+        assert_eq!(
+            stale.merge(newer),
+            Validators {
+                etag: Some("\"v2\"".to_owned()),
+                last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned()),
+            }
+        );
+    }
+}